@@ -1,26 +1,51 @@
-use std::fs::File;
-use std::io::{ErrorKind, Write};
+use std::env;
+use std::io::ErrorKind;
 use std::net::{Ipv4Addr, SocketAddr};
-use std::path::Path;
-use std::{env, fs};
+use std::sync::Arc;
 
 use anyhow::Context;
-use http::{Method, Request, Response};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use http::{
+    Body, CatchPanic, Connection, ConnectionError, FileRoot, FileRootError, HeaderName, Headers,
+    Logger, Method, Params, Path, Request, RequestId, RequestTarget, Response, ResponseBuilder,
+    Router, State, StaticFiles, Status, Timing,
+};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
-use tokio::runtime;
+use tokio::{runtime, task};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() > 1 && &args[1] == "--directory" {
-        let value = &args[2];
-        env::set_var("DIRECTORY", value);
-    } else {
-        let value = "/tmp";
-        env::set_var("DIRECTORY", value);
-    };
+    let mut directory = "/tmp".to_string();
+    let mut mount_point = "/files/".to_string();
+    let mut sniffs_content = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--directory" => {
+                directory = args
+                    .next()
+                    .context("Expecting a directory path after `--directory`")?;
+            }
+            "--mount-point" => {
+                mount_point = args
+                    .next()
+                    .context("Expecting a path prefix after `--mount-point`")?;
+            }
+            "--sniff-content" => sniffs_content = true,
+            arg => anyhow::bail!("Unexpected argument: {arg}"),
+        }
+    }
+    let file_root = FileRoot::new(&directory)
+        .with_context(|| format!("Failed to open directory to serve files from: {directory}"))?;
+    let static_files = StaticFiles::new(file_root)
+        .set_mount_point(mount_point)
+        .set_sniffs_content(sniffs_content);
+    println!(
+        "Serving files from {directory} at {}",
+        static_files.mount_point()
+    );
+    let router = Arc::new(make_router(static_files));
 
     let ipv4_address = Ipv4Addr::LOCALHOST;
     let port: u16 = 4221;
@@ -33,124 +58,179 @@ async fn main() -> anyhow::Result<()> {
 
     let runtime = runtime::Builder::new_multi_thread()
         .worker_threads(4)
+        .enable_all()
         .build()
         .context("Attempting to build `tokio` runtime with 4 workers")?;
 
     loop {
-        let (mut stream, client_address) = listener
+        let (stream, client_address) = listener
             .accept()
             .await
             .context("Failed to accept new connection")?;
         println!("Accepted connection from client at address: {client_address}");
 
+        let router = Arc::clone(&router);
         runtime.spawn(async move {
-            let mut buffer = [0u8; 1024];
+            let mut connection = Connection::new(stream);
+
             loop {
-                match stream.read(&mut buffer).await {
-                    Ok(0) => println!("No bytes to read"),
-                    Ok(number_of_bytes) => println!("Read {number_of_bytes} bytes into buffer"),
-                    Err(error) => {
-                        eprintln!("Failed to read bytes because of error: {error}");
+                let response = match connection.read_request().await {
+                    Ok(None) => {
+                        println!("Client at address {client_address} closed the connection");
+                        break;
+                    }
+                    Ok(Some(request)) => generate_response(request, &router).await,
+                    Err(ConnectionError::InvalidRequest(error)) => {
+                        eprintln!(
+                            "Failed to parse request from client at address {client_address} because of error: {error}"
+                        );
+                        Response::from_error(&error).build()
                     }
-                };
-
-                let directory =
-                    env::var("DIRECTORY").expect("`DIRECTORY` environment variable has been set");
-
-                let response = match Request::parse(buffer.as_slice()) {
                     Err(error) => {
-                        eprintln!("Failed to parse bytes because of error: {error}");
-                        Response::internal_server_error().build()
+                        eprintln!("Failed to read request because of error: {error}");
+                        break;
                     }
-                    Ok((_, request)) => generate_response(request, &directory),
                 };
 
-                let response_string = response.to_string();
+                if let Err(error) = connection.write_response(response).await {
+                    eprintln!("Failed to write bytes because of error: {error}");
+                    break;
+                }
 
-                match stream.write(response_string.as_bytes()).await {
-                    Ok(0) => println!("No bytes to write"),
-                    Ok(number_of_bytes) => println!("Wrote {number_of_bytes} bytes"),
-                    Err(error) => eprintln!("Failed to write bytes because of error: {error}"),
-                };
+                if !connection.is_persistent() {
+                    println!("Closing connection to client at address {client_address}");
+                    break;
+                }
             }
         });
     }
 }
 
-fn generate_response(request: Request, directory: &str) -> Response {
-    let response_builder = match request {
-        request if request.target() == "/" => {
-            println!("Received request: {request:?}");
-            Response::ok()
-        }
-        request if request.target().starts_with("/echo/") => {
-            let target_suffix = request
-                .target()
-                .strip_prefix("/echo/")
-                .expect("We've already checked that this string starts with '/echo/'");
-            println!("Received request: {request:?}");
-            Response::ok().set_body(target_suffix.as_str())
-        }
-        request if request.target().starts_with("/files/") && request.method() == Method::Get => {
-            let requested_file_name = request
-                .target()
-                .strip_prefix("/files/")
-                .expect("We've already checked that this string starts with '/files/'");
-            let requested_path = Path::new(&directory).join(requested_file_name.as_str());
-            match fs::read(requested_path) {
-                Ok(content) => Response::ok().set_body(content),
-                Err(error) if error.kind() == ErrorKind::NotFound => Response::not_found(),
-                Err(error) => {
-                    eprintln!(
-                        "While trying to read from path: {}",
-                        requested_file_name.as_str()
-                    );
-                    eprintln!("Encountered error: {error}");
-                    Response::internal_server_error()
-                }
+fn make_router(static_files: StaticFiles) -> Router {
+    let mount_point = static_files.mount_point().to_string();
+    let router = Router::new()
+        .layer(RequestId::new())
+        .layer(Logger)
+        .layer(CatchPanic)
+        .layer(Timing)
+        .set_state(static_files)
+        .get("/echo/*text", |params: Params| async move {
+            params.get("text").unwrap_or_default().to_string()
+        })
+        .get("/user-agent", |headers: Headers| async move {
+            match headers.user_agent() {
+                Some(user_agent) => Response::ok().set_body(user_agent.to_string()),
+                None => Response::bad_request()
+                    .set_body("Requests to /user-agent should have a User-Agent header\n"),
             }
-        }
-        request if request.target().starts_with("/files/") && request.method() == Method::Post => {
-            let requested_file_name = request
-                .target()
-                .strip_prefix("/files/")
-                .expect("We've already checked that this string starts with '/files/'");
-            let requested_path = Path::new(&directory).join(requested_file_name.as_str());
-            let body = request
-                .body()
-                .expect("POST requests to /files/ should have a body");
-            match File::create_new(&requested_path) {
-                Ok(mut file) => {
-                    file.write_all(body.as_bytes())
-                        .expect("Writing to a newly created file should succeed");
-                    Response::created()
-                }
-                Err(error) if error.kind() == ErrorKind::AlreadyExists => Response::bad_request(),
-                Err(error) => {
-                    eprintln!(
-                        "Failed to create a new file at: {}",
-                        requested_path.to_string_lossy()
-                    );
-                    eprintln!("Because of error: {error}");
-                    Response::internal_server_error()
-                }
+        })
+        .post(&format!("{mount_point}*path"), create_file)
+        .get(&format!("{mount_point}*path"), serve_file)
+        // Only CONNECT requests reach the fallback without a path, and we don't tunnel
+        .set_fallback(|request: Request| async move {
+            match request.target() {
+                RequestTarget::Authority(_) => Response::not_implemented(),
+                _ => Response::not_found(),
             }
+        });
+
+    // A site mounted at the root serves its own index
+    match mount_point.as_str() {
+        "/" => router,
+        _ => router.get("/", || async { Status::Ok }),
+    }
+}
+
+async fn generate_response(request: Request, router: &Router) -> Response {
+    let accept_encoding = request.headers().get_combined(&HeaderName::AcceptEncoding);
+    router
+        .handle(request)
+        .await
+        .compress(accept_encoding.as_ref())
+        .build()
+}
+
+async fn serve_file(
+    State(static_files): State<StaticFiles>,
+    method: Method,
+    path: Option<Path>,
+    headers: Headers,
+) -> ResponseBuilder {
+    let served = match path {
+        Some(path) => static_files.serve(&method, &path, &headers).await,
+        None => None,
+    };
+    match served {
+        Some(Ok(response_builder)) => response_builder,
+        Some(Err(error)) => file_root_error_response(error),
+        None => Response::not_found(),
+    }
+}
+
+async fn create_file(
+    State(static_files): State<StaticFiles>,
+    params: Params,
+    body: Option<Body>,
+) -> ResponseBuilder {
+    let requested_file_name = params.get("path").unwrap_or_default().to_string();
+    let file_root = static_files.file_root().clone();
+    let requested_path =
+        task::spawn_blocking(move || file_root.resolve_new(&requested_file_name)).await;
+    let requested_path = match requested_path {
+        Ok(Ok(requested_path)) => requested_path,
+        Ok(Err(error)) => return file_root_error_response(error),
+        Err(error) => {
+            eprintln!("Failed to resolve a path under the file root because of error: {error}");
+            return Response::internal_server_error();
         }
-        request if request.target() == "/user-agent" => {
-            println!("Received request: {request:?}");
-            let user_agent = request
-                .headers()
-                .user_agent()
-                .expect("Requests to the '/user-agent' endpoint should have a 'User-Agent' header")
-                .to_string();
-            Response::ok().set_body(user_agent)
+    };
+    let Some(body) = body else {
+        return Response::bad_request().set_body(format!(
+            "POST requests to {} should have a body\n",
+            static_files.mount_point()
+        ));
+    };
+    let new_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&requested_path)
+        .await;
+    match new_file {
+        // Flushing waits for the write to finish, rather than for it to be handed to a thread
+        Ok(mut file) => match file
+            .write_all(body.as_bytes())
+            .await
+            .and(file.flush().await)
+        {
+            Ok(()) => Response::created(),
+            Err(error) => {
+                eprintln!(
+                    "Failed to write to new file at: {}",
+                    requested_path.to_string_lossy()
+                );
+                eprintln!("Because of error: {error}");
+                Response::internal_server_error()
+            }
+        },
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => Response::bad_request(),
+        Err(error) => {
+            eprintln!(
+                "Failed to create a new file at: {}",
+                requested_path.to_string_lossy()
+            );
+            eprintln!("Because of error: {error}");
+            Response::internal_server_error()
         }
-        request => {
-            println!("Received request: {request:?}");
-            Response::not_found()
+    }
+}
+
+fn file_root_error_response(error: FileRootError) -> ResponseBuilder {
+    match error {
+        FileRootError::Forbidden => Response::forbidden(),
+        FileRootError::NotFound => Response::not_found(),
+        FileRootError::Io(error) => {
+            eprintln!("Failed to resolve a path under the file root because of error: {error}");
+            Response::internal_server_error()
         }
-    };
-    let response = response_builder.build();
-    println!("Generated response: {response}");
-    response
+    }
 }
@@ -1,23 +1,53 @@
-use std::fmt;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+};
+
+use crate::headers::Headers;
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+const CHANNEL_CAPACITY: usize = 16;
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum Body {
     PlainText(String),
     OctetStream(Vec<u8>),
+    Stream(BodyStream),
 }
 
 impl Body {
+    // Streams of unknown length report a length of zero
     pub fn len(&self) -> usize {
         match self {
             Body::PlainText(string) => string.len(),
             Body::OctetStream(bytes) => bytes.len(),
+            Body::Stream(body_stream) => body_stream.length().unwrap_or_default(),
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Body::Stream(body_stream) => body_stream.length() == Some(0),
+            _ => self.len() == 0,
+        }
+    }
+
+    // Streams are not held in memory, so have no bytes to return
     pub fn as_bytes(&self) -> &[u8] {
         match self {
             Self::OctetStream(bytes) => bytes,
             Self::PlainText(string) => string.as_bytes(),
+            Self::Stream(_) => &[],
         }
     }
 }
@@ -30,6 +60,8 @@ impl fmt::Display for Body {
                 Ok(string) => write!(f, "{string}"),
                 Err(_) => write!(f, "{bytes:?}"),
             },
+            // Streams are written to the connection separately
+            Body::Stream(_) => Ok(()),
         }
     }
 }
@@ -52,3 +84,262 @@ impl From<Vec<u8>> for Body {
         Self::OctetStream(body)
     }
 }
+
+impl From<BodyStream> for Body {
+    fn from(body: BodyStream) -> Self {
+        Self::Stream(body)
+    }
+}
+
+// A body produced incrementally by an async source, like a file or a channel fed by another
+// task, so that it never has to be held in memory in full.
+//
+// Clones share the same source, which can only be consumed once. Two streams are equal if they
+// share a source.
+#[derive(Clone)]
+pub struct BodyStream {
+    length: Option<usize>,
+    trailers: Option<Headers>,
+    source: Arc<Mutex<Option<Source>>>,
+}
+
+impl BodyStream {
+    pub fn from_reader(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self::new(Source::Reader(Box::pin(reader)))
+    }
+
+    // Returns a stream whose chunks, and optionally trailers, are sent by the `BodySender`
+    pub fn channel() -> (BodySender, Self) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        (BodySender(sender), Self::new(Source::Channel(receiver)))
+    }
+
+    // Streams with a known length are sent with `Content-Length` rather than chunked
+    pub fn set_length(mut self, length: usize) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    // Trailers are only sent if the body is chunked
+    pub fn set_trailers(mut self, trailers: Headers) -> Self {
+        self.trailers = Some(trailers);
+        self
+    }
+
+    pub fn length(&self) -> Option<usize> {
+        self.length
+    }
+
+    pub(crate) fn take_source(&self) -> Option<BodySource> {
+        let source = self
+            .source
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()?;
+        let body_source = BodySource {
+            source,
+            trailers: self.trailers.clone(),
+            is_finished: false,
+        };
+        Some(body_source)
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            length: None,
+            trailers: None,
+            source: Arc::new(Mutex::new(Some(source))),
+        }
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyStream")
+            .field("length", &self.length)
+            .field("trailers", &self.trailers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for BodyStream {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.source, &other.source)
+    }
+}
+
+impl Eq for BodyStream {}
+
+impl Ord for BodyStream {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Arc::as_ptr(&self.source).cmp(&Arc::as_ptr(&other.source))
+    }
+}
+
+impl PartialOrd for BodyStream {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for BodyStream {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.source).hash(state);
+    }
+}
+
+// Sends the chunks of a `BodyStream`. The body must be ended with `finish` or `send_trailers`:
+// a sender dropped before then, say because the task producing the body failed, leaves the body
+// cut short, which the `Connection` makes clear to the client by closing the connection rather
+// than ending the body normally.
+#[derive(Debug)]
+pub struct BodySender(mpsc::Sender<Frame>);
+
+impl BodySender {
+    pub async fn send(&self, chunk: impl Into<Bytes>) -> io::Result<()> {
+        let chunk = chunk.into();
+        self.send_frame(Frame::Data(chunk)).await
+    }
+
+    // Ends the body after the chunks sent so far
+    pub async fn finish(self) -> io::Result<()> {
+        self.send_frame(Frame::End).await
+    }
+
+    // Ends the body, sending `trailers` after the last chunk
+    pub async fn send_trailers(self, trailers: Headers) -> io::Result<()> {
+        self.send_frame(Frame::Trailers(trailers)).await
+    }
+
+    async fn send_frame(&self, frame: Frame) -> io::Result<()> {
+        self.0.send(frame).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "The body stream was dropped before it was fully sent",
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    Data(Bytes),
+    Trailers(Headers),
+    End,
+}
+
+enum Source {
+    Reader(Pin<Box<dyn AsyncRead + Send>>),
+    Channel(mpsc::Receiver<Frame>),
+}
+
+pub(crate) struct BodySource {
+    source: Source,
+    trailers: Option<Headers>,
+    is_finished: bool,
+}
+
+impl BodySource {
+    // Returns `None` once the source is exhausted; never returns an empty chunk. Fails if a
+    // channel's sender is dropped before it ends the body.
+    pub async fn next_chunk(&mut self) -> io::Result<Option<Bytes>> {
+        if self.is_finished {
+            return Ok(None);
+        }
+        match &mut self.source {
+            Source::Reader(reader) => {
+                let mut buffer = BytesMut::with_capacity(READ_CHUNK_SIZE);
+                match reader.read_buf(&mut buffer).await? {
+                    0 => Ok(None),
+                    _ => Ok(Some(buffer.freeze())),
+                }
+            }
+            Source::Channel(receiver) => loop {
+                match receiver.recv().await {
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "The body sender was dropped before it ended the body",
+                        ))
+                    }
+                    Some(Frame::Data(chunk)) if chunk.is_empty() => continue,
+                    Some(Frame::Data(chunk)) => return Ok(Some(chunk)),
+                    Some(Frame::Trailers(trailers)) => {
+                        self.trailers = Some(trailers);
+                        self.is_finished = true;
+                        return Ok(None);
+                    }
+                    Some(Frame::End) => {
+                        self.is_finished = true;
+                        return Ok(None);
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.trailers.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stream_can_only_be_consumed_once() {
+        let body_stream = BodyStream::from_reader(&b"abc"[..]);
+        let clone = body_stream.clone();
+
+        let mut source = body_stream
+            .take_source()
+            .expect("Source has not been taken");
+        let chunk = source.next_chunk().await.unwrap();
+
+        assert_eq!(chunk, Some(Bytes::from_static(b"abc")));
+        assert!(clone.take_source().is_none());
+        assert_eq!(body_stream, clone);
+    }
+
+    #[tokio::test]
+    async fn channel_stream_yields_sent_chunks_and_trailers() {
+        let (sender, body_stream) = BodyStream::channel();
+        let mut source = body_stream.take_source().unwrap();
+
+        tokio::spawn(async move {
+            sender.send("ab").await.unwrap();
+            sender.send("").await.unwrap();
+            sender.send("cd").await.unwrap();
+            let trailers = Headers::default().set_content_type("text/plain");
+            sender.send_trailers(trailers).await.unwrap();
+        });
+
+        assert_eq!(source.next_chunk().await.unwrap(), Some(Bytes::from("ab")));
+        assert_eq!(source.next_chunk().await.unwrap(), Some(Bytes::from("cd")));
+        assert_eq!(source.next_chunk().await.unwrap(), None);
+        assert!(source.trailers().is_some());
+        assert_eq!(source.next_chunk().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn channel_stream_fails_if_sender_is_dropped_early() {
+        let (sender, body_stream) = BodyStream::channel();
+        let mut source = body_stream.take_source().unwrap();
+
+        tokio::spawn(async move {
+            sender.send("ab").await.unwrap();
+        });
+
+        assert_eq!(source.next_chunk().await.unwrap(), Some(Bytes::from("ab")));
+        let error = source.next_chunk().await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let (sender, body_stream) = BodyStream::channel();
+        let mut source = body_stream.take_source().unwrap();
+
+        sender.finish().await.unwrap();
+
+        assert_eq!(source.next_chunk().await.unwrap(), None);
+    }
+}
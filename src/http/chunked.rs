@@ -0,0 +1,253 @@
+use bytes::{BufMut, BytesMut};
+use nom::{
+    bytes::streaming::{take, take_till, take_till1, take_while1},
+    character::{is_hex_digit, is_space},
+    combinator, error, multi, sequence,
+    sequence::Tuple,
+    IResult,
+};
+
+use crate::{body::Body, error::Error, headers::Headers, parsing_utils};
+
+// Decodes a body sent with `Transfer-Encoding: chunked`. The decoded body can be at most
+// `max_body_size` bytes, and its chunk-size lines, extensions and trailers at most
+// `max_framing_size` bytes; either is rejected as soon as a chunk passes its limit.
+// https://www.rfc-editor.org/rfc/rfc9112#section-7.1
+pub fn parse(
+    bytes: &[u8],
+    max_body_size: usize,
+    max_framing_size: usize,
+) -> IResult<&[u8], Body, Error> {
+    let mut body = Vec::new();
+    let mut framing_left = max_framing_size;
+    let mut remainder = bytes;
+
+    loop {
+        let (after_size, chunk_size) =
+            parse_framing(chunk_size_line, remainder, &mut framing_left)?;
+        if chunk_size == 0 {
+            let (after_trailers, _) =
+                parse_framing(trailer_section, after_size, &mut framing_left)?;
+            return Ok((after_trailers, Body::OctetStream(body)));
+        }
+        if chunk_size > max_body_size - body.len() {
+            return Err(nom::Err::Error(Error::BodyTooLarge));
+        }
+
+        let (after_chunk, (chunk_data, _)) = (take(chunk_size), parsing_utils::crlf)
+            .parse(after_size)
+            .map_err(|error| error.map(|_| Error::MalformedChunkedBody))?;
+        body.extend_from_slice(chunk_data);
+        remainder = after_chunk;
+    }
+}
+
+// Encodes a single, non-empty, chunk into `buffer`
+pub fn encode_chunk(chunk: &[u8], buffer: &mut BytesMut) {
+    debug_assert!(
+        !chunk.is_empty(),
+        "An empty chunk marks the end of the body"
+    );
+    buffer.put(format!("{:x}\r\n", chunk.len()).as_bytes());
+    buffer.put(chunk);
+    buffer.put(&b"\r\n"[..]);
+}
+
+// Encodes the zero-length chunk that ends the body, followed by any trailers
+pub fn encode_last_chunk(trailers: Option<&Headers>, buffer: &mut BytesMut) {
+    buffer.put(&b"0\r\n"[..]);
+    if let Some(trailers) = trailers {
+        buffer.put(trailers.to_string().as_bytes());
+    }
+    buffer.put(&b"\r\n"[..]);
+}
+
+// Runs `parser` on at most the framing bytes left, taking the bytes it consumes from them
+fn parse_framing<'a, O>(
+    parser: impl FnOnce(&'a [u8]) -> IResult<&'a [u8], O>,
+    bytes: &'a [u8],
+    framing_left: &mut usize,
+) -> IResult<&'a [u8], O, Error> {
+    let is_truncated = bytes.len() > *framing_left;
+    let bounded_bytes = &bytes[..bytes.len().min(*framing_left)];
+    match parser(bounded_bytes) {
+        Ok((remainder, output)) => {
+            let consumed = bounded_bytes.len() - remainder.len();
+            *framing_left -= consumed;
+            Ok((&bytes[consumed..], output))
+        }
+        Err(nom::Err::Incomplete(_)) if is_truncated => Err(nom::Err::Error(Error::BodyTooLarge)),
+        Err(error) => Err(error.map(|_| Error::MalformedChunkedBody)),
+    }
+}
+
+fn chunk_size_line(bytes: &[u8]) -> IResult<&[u8], usize> {
+    let (remainder, (chunk_size, _, _)) =
+        (chunk_size, chunk_extensions, parsing_utils::crlf).parse(bytes)?;
+    Ok((remainder, chunk_size))
+}
+
+fn chunk_size(bytes: &[u8]) -> IResult<&[u8], usize> {
+    combinator::map_res(take_while1(is_hex_digit), |hex_digits: &[u8]| {
+        // Hex digits are ASCII so this is always valid UTF-8
+        let hex_string = String::from_utf8_lossy(hex_digits);
+        usize::from_str_radix(&hex_string, 16)
+    })(bytes)
+}
+
+// Chunk extensions have no meaning to us, so they are validated loosely and then ignored
+// https://www.rfc-editor.org/rfc/rfc9112#section-7.1.1
+fn chunk_extensions(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (remainder, extensions) = take_till(|byte| byte == b'\r')(bytes)?;
+    let first_non_whitespace = extensions.iter().find(|byte| !is_space(**byte));
+    match first_non_whitespace {
+        None | Some(b';') => Ok((remainder, extensions)),
+        Some(_) => Err(nom::Err::Error(error::Error::new(
+            bytes,
+            error::ErrorKind::Verify,
+        ))),
+    }
+}
+
+// Trailer fields are consumed so the connection stays in sync, but are not merged into
+// the request's headers
+// https://www.rfc-editor.org/rfc/rfc9112#section-7.1.2
+fn trailer_section(bytes: &[u8]) -> IResult<&[u8], Vec<&[u8]>> {
+    let trailer_field = sequence::terminated(take_till1(|byte| byte == b'\r'), parsing_utils::crlf);
+    sequence::terminated(multi::many0(trailer_field), parsing_utils::crlf)(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> IResult<&[u8], Body, Error> {
+        super::parse(bytes, usize::MAX, usize::MAX)
+    }
+
+    #[test]
+    fn decode_chunked_body() {
+        let bytes = b"4\r\nWiki\r\n6\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\r\nGET";
+
+        let (remainder, body) = parse(bytes).expect("Body is validly chunked");
+
+        assert_eq!(remainder, b"GET");
+        assert_eq!(body.as_bytes(), b"Wikipedia in \r\n\r\nchunks.");
+    }
+
+    #[test]
+    fn decode_chunked_body_with_extensions_and_trailers() {
+        let bytes = b"3;name=value\r\nabc\r\n0 ; last\r\nExpires: never\r\nX-Checksum: 1\r\n\r\n";
+
+        let (remainder, body) = parse(bytes).expect("Body is validly chunked");
+
+        assert!(remainder.is_empty());
+        assert_eq!(body.as_bytes(), b"abc");
+    }
+
+    #[test]
+    fn partial_chunked_body_is_incomplete() {
+        let bytes = b"a\r\n01234";
+
+        let result = parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn missing_last_chunk_is_incomplete() {
+        let bytes = b"3\r\nabc\r\n";
+
+        let result = parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn invalid_chunk_size_is_an_error() {
+        let bytes = b"xyz\r\nabc\r\n0\r\n\r\n";
+
+        let result = parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn chunk_size_overflow_is_an_error() {
+        let bytes = b"fffffffffffffffffffff\r\n";
+
+        let result = parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn chunk_data_longer_than_chunk_size_is_an_error() {
+        let bytes = b"2\r\nabc\r\n0\r\n\r\n";
+
+        let result = parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn body_limit_applies_to_decoded_length() {
+        let bytes = b"1\r\na\r\n1;ext=value\r\nb\r\n0\r\nTrailer: x\r\n\r\n";
+
+        let (_, body) = super::parse(bytes, 2, 64).expect("Body is within the limits");
+
+        assert_eq!(body.as_bytes(), b"ab");
+        assert_eq!(
+            super::parse(bytes, 1, 64),
+            Err(nom::Err::Error(Error::BodyTooLarge))
+        );
+    }
+
+    #[test]
+    fn chunks_larger_than_the_limit_are_rejected_before_they_arrive() {
+        let bytes = b"100\r\nabc";
+
+        let result = super::parse(bytes, 255, 64);
+
+        assert_eq!(result, Err(nom::Err::Error(Error::BodyTooLarge)));
+    }
+
+    #[test]
+    fn framing_limit_applies_to_size_lines_and_trailers() {
+        let extensions = b"1;a-very-long-chunk-extension-that-never-ends";
+        let trailers = b"0\r\nX-Trailer: a-very-long-trailer-value\r\n\r\n";
+
+        assert_eq!(
+            super::parse(extensions, 64, 16),
+            Err(nom::Err::Error(Error::BodyTooLarge))
+        );
+        assert_eq!(
+            super::parse(trailers, 64, 16),
+            Err(nom::Err::Error(Error::BodyTooLarge))
+        );
+    }
+
+    #[test]
+    fn encode_and_decode_chunks() {
+        let mut buffer = BytesMut::new();
+
+        encode_chunk(b"Hello, ", &mut buffer);
+        encode_chunk(b"World!", &mut buffer);
+        encode_last_chunk(None, &mut buffer);
+
+        assert_eq!(&buffer[..], b"7\r\nHello, \r\n6\r\nWorld!\r\n0\r\n\r\n");
+        let (remainder, body) = parse(&buffer).expect("Encoded body is validly chunked");
+        assert!(remainder.is_empty());
+        assert_eq!(body.as_bytes(), b"Hello, World!");
+    }
+
+    #[test]
+    fn encode_last_chunk_with_trailers() {
+        let mut buffer = BytesMut::new();
+        let trailers = Headers::default().set_content_type("text/plain");
+
+        encode_last_chunk(Some(&trailers), &mut buffer);
+
+        assert_eq!(&buffer[..], b"0\r\nContent-Type: text/plain\r\n\r\n");
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+
+use crate::headers::HeaderValue;
+
+// Bodies shorter than this gain little from compression, and may even grow
+pub const MIN_COMPRESSIBLE_LENGTH: usize = 256;
+
+// Media types whose formats are already compressed
+const INCOMPRESSIBLE_MEDIA_TYPE_PREFIXES: &[&str] = &[
+    "image/",
+    "audio/",
+    "video/",
+    "font/woff",
+    "application/gzip",
+    "application/x-gzip",
+    "application/zip",
+    "application/zstd",
+    "application/x-bzip2",
+    "application/x-7z-compressed",
+    "application/x-rar-compressed",
+];
+
+// Image formats that are text, and so compress well
+const COMPRESSIBLE_IMAGE_TYPES: &[&str] = &["image/svg+xml"];
+
+// https://www.rfc-editor.org/rfc/rfc9110#section-8.4.1
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ContentCoding {
+    #[cfg(feature = "brotli")]
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl ContentCoding {
+    // In order of preference when the client accepts several codings equally
+    const SUPPORTED: &'static [Self] = &[
+        #[cfg(feature = "brotli")]
+        Self::Brotli,
+        Self::Gzip,
+        Self::Deflate,
+    ];
+
+    // Chooses the supported coding with the highest q-value in an `Accept-Encoding` header,
+    // returning `None` if the client doesn't accept any of them
+    // https://www.rfc-editor.org/rfc/rfc9110#section-12.5.3
+    pub fn negotiate(accept_encoding: &HeaderValue) -> Option<Self> {
+        let preferences = parse_accept_encoding(&accept_encoding.to_string());
+        let q_value_of = |name: &str| {
+            preferences
+                .iter()
+                .find(|(coding, _)| coding == name)
+                .or_else(|| preferences.iter().find(|(coding, _)| coding == "*"))
+                .map_or(0.0, |(_, q_value)| *q_value)
+        };
+
+        Self::SUPPORTED
+            .iter()
+            .map(|coding| (*coding, q_value_of(coding.name())))
+            .filter(|(_, q_value)| *q_value > 0.0)
+            // `max_by` returns the last maximum, but we want the first
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(coding, _)| coding)
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "brotli")]
+            Self::Brotli => {
+                let mut encoded = Vec::new();
+                let parameters = brotli::enc::BrotliEncoderParams::default();
+                brotli::BrotliCompress(&mut &bytes[..], &mut encoded, &parameters)?;
+                Ok(encoded)
+            }
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            // The "deflate" content coding is actually the zlib format
+            // https://www.rfc-editor.org/rfc/rfc9110#section-8.4.1.2
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+}
+
+impl fmt::Display for ContentCoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl From<ContentCoding> for HeaderValue {
+    fn from(content_coding: ContentCoding) -> Self {
+        HeaderValue::new(content_coding.name())
+    }
+}
+
+// Whether a body of this `Content-Type` is worth compressing
+pub fn is_compressible(content_type: &HeaderValue) -> bool {
+    let content_type = content_type.to_string().to_ascii_lowercase();
+    COMPRESSIBLE_IMAGE_TYPES
+        .iter()
+        .any(|media_type| content_type.starts_with(media_type))
+        || !INCOMPRESSIBLE_MEDIA_TYPE_PREFIXES
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix))
+}
+
+// Parses codings and their q-values, ignoring any with a malformed q-value
+fn parse_accept_encoding(accept_encoding: &str) -> Vec<(String, f32)> {
+    accept_encoding
+        .split(',')
+        .filter_map(|element| {
+            let mut parameters = element.split(';');
+            let coding = parameters.next()?.trim().to_ascii_lowercase();
+            if coding.is_empty() {
+                return None;
+            }
+            let q_value = match parameters.find_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("q").then(|| value.trim())
+            }) {
+                None => 1.0,
+                Some(value) => match value.parse::<f32>() {
+                    Ok(q_value) if (0.0..=1.0).contains(&q_value) => q_value,
+                    _ => return None,
+                },
+            };
+            Some((coding, q_value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::{GzDecoder, ZlibDecoder};
+
+    use super::*;
+
+    #[test]
+    fn negotiate_single_coding() {
+        let accept_encoding = HeaderValue::new("gzip");
+
+        assert_eq!(
+            ContentCoding::negotiate(&accept_encoding),
+            Some(ContentCoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn negotiate_highest_q_value() {
+        let accept_encoding = HeaderValue::new("gzip;q=0.5, deflate; q=0.8, identity");
+
+        assert_eq!(
+            ContentCoding::negotiate(&accept_encoding),
+            Some(ContentCoding::Deflate)
+        );
+    }
+
+    #[test]
+    fn negotiate_ignores_unsupported_and_refused_codings() {
+        let accept_encoding = HeaderValue::new("zstd, gzip;q=0, invalid-1, deflate;q=2");
+
+        assert_eq!(ContentCoding::negotiate(&accept_encoding), None);
+    }
+
+    #[test]
+    fn negotiate_wildcard() {
+        let accept_encoding = HeaderValue::new("deflate;q=0.1, *;q=0.5");
+
+        assert_ne!(
+            ContentCoding::negotiate(&accept_encoding),
+            Some(ContentCoding::Deflate)
+        );
+        assert!(ContentCoding::negotiate(&accept_encoding).is_some());
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let bytes = b"Hello, World! Hello, World! Hello, World!";
+
+        let encoded = ContentCoding::Gzip.encode(bytes).unwrap();
+
+        let mut decoded = Vec::new();
+        GzDecoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, bytes);
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let bytes = b"Hello, World! Hello, World! Hello, World!";
+
+        let encoded = ContentCoding::Deflate.encode(bytes).unwrap();
+
+        let mut decoded = Vec::new();
+        ZlibDecoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, bytes);
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_round_trip() {
+        let bytes = b"Hello, World! Hello, World! Hello, World!";
+
+        let encoded = ContentCoding::Brotli.encode(bytes).unwrap();
+
+        let mut decoded = Vec::new();
+        brotli::BrotliDecompress(&mut &encoded[..], &mut decoded).unwrap();
+        assert_eq!(decoded, bytes);
+    }
+
+    #[test]
+    fn already_compressed_types_are_not_compressible() {
+        assert!(is_compressible(&HeaderValue::new("text/plain")));
+        assert!(is_compressible(&HeaderValue::new("image/svg+xml")));
+        assert!(!is_compressible(&HeaderValue::new("image/png")));
+        assert!(!is_compressible(&HeaderValue::new("application/zip")));
+    }
+}
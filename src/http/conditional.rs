@@ -0,0 +1,390 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    headers::{HeaderName, HeaderValue, Headers},
+    http_date,
+    method::Method,
+};
+
+// An opaque validator that changes whenever the representation it was sent with does
+// https://www.rfc-editor.org/rfc/rfc9110#section-8.8.3
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct EntityTag {
+    // Weak tags only promise an equivalent representation, not an identical one
+    is_weak: bool,
+    opaque_tag: String,
+}
+
+impl EntityTag {
+    pub fn strong(opaque_tag: impl Into<String>) -> Self {
+        Self {
+            is_weak: false,
+            opaque_tag: opaque_tag.into(),
+        }
+    }
+
+    pub fn weak(opaque_tag: impl Into<String>) -> Self {
+        Self {
+            is_weak: true,
+            opaque_tag: opaque_tag.into(),
+        }
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.is_weak
+    }
+
+    pub fn opaque_tag(&self) -> &str {
+        &self.opaque_tag
+    }
+
+    // Parses a single tag, like `"abc"` or `W/"abc"`
+    pub fn parse(entity_tag: &str) -> Option<Self> {
+        let (is_weak, quoted) = match entity_tag.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, entity_tag),
+        };
+        let opaque_tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        // etagc = %x21 / %x23-7E / obs-text
+        let is_valid = opaque_tag
+            .bytes()
+            .all(|byte| byte == 0x21 || (0x23..=0x7e).contains(&byte) || byte >= 0x80);
+        is_valid.then(|| Self {
+            is_weak,
+            opaque_tag: opaque_tag.to_string(),
+        })
+    }
+
+    // https://www.rfc-editor.org/rfc/rfc9110#section-8.8.3.2
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.is_weak && !other.is_weak && self.opaque_tag == other.opaque_tag
+    }
+
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.opaque_tag == other.opaque_tag
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_weak {
+            true => write!(f, "W/\"{}\"", self.opaque_tag),
+            false => write!(f, "\"{}\"", self.opaque_tag),
+        }
+    }
+}
+
+impl From<EntityTag> for HeaderValue {
+    fn from(entity_tag: EntityTag) -> Self {
+        HeaderValue::new(entity_tag.to_string())
+    }
+}
+
+// What to do with a request once its preconditions have been evaluated against the current
+// representation
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Precondition {
+    // Respond as if there were no preconditions
+    Passed,
+    // Respond with 304, since the client's cached copy is current
+    NotModified,
+    // Respond with 412 without performing the request
+    Failed,
+}
+
+// Evaluates `If-Match`, `If-Unmodified-Since`, `If-None-Match` and `If-Modified-Since`, in that
+// order, against the validators of the current representation. Headers that can't be parsed are
+// ignored. `If-Range` only applies to `Range`, so is evaluated by `is_if_range_fresh`.
+// https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2
+pub fn evaluate(
+    method: &Method,
+    request_headers: &Headers,
+    etag: Option<&EntityTag>,
+    last_modified: Option<SystemTime>,
+) -> Precondition {
+    let is_get_or_head = matches!(method, Method::Get | Method::Head);
+    let last_modified = last_modified.map(truncate_to_seconds);
+
+    if let Some(if_match) = entity_tags(request_headers, &HeaderName::IfMatch) {
+        if !if_match.contains(etag, EntityTag::strong_eq) {
+            return Precondition::Failed;
+        }
+    } else if let Some(if_unmodified_since) = date(request_headers.if_unmodified_since()) {
+        let is_unmodified =
+            last_modified.is_some_and(|last_modified| last_modified <= if_unmodified_since);
+        if !is_unmodified {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = entity_tags(request_headers, &HeaderName::IfNoneMatch) {
+        if if_none_match.contains(etag, EntityTag::weak_eq) {
+            return match is_get_or_head {
+                true => Precondition::NotModified,
+                false => Precondition::Failed,
+            };
+        }
+    } else if let Some(if_modified_since) = date(request_headers.if_modified_since()) {
+        let is_modified =
+            last_modified.map_or(true, |last_modified| last_modified > if_modified_since);
+        if is_get_or_head && !is_modified {
+            return Precondition::NotModified;
+        }
+    }
+
+    Precondition::Passed
+}
+
+// A `Range` is only used if the `If-Range` validator, if any, shows the client's partial copy is
+// of the current representation. Entity tags must match strongly and dates exactly.
+// https://www.rfc-editor.org/rfc/rfc9110#section-13.1.5
+pub fn is_if_range_fresh(
+    if_range: Option<&HeaderValue>,
+    etag: Option<&EntityTag>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let Some(if_range) = if_range else {
+        return true;
+    };
+    let if_range = if_range.to_string();
+    match EntityTag::parse(&if_range) {
+        Some(if_range) => etag.is_some_and(|etag| etag.strong_eq(&if_range)),
+        None => {
+            let if_range = http_date::parse(&if_range);
+            if_range.is_some() && if_range == last_modified.map(truncate_to_seconds)
+        }
+    }
+}
+
+// The tags in an `If-Match` or `If-None-Match` header, where "*" matches any current
+// representation
+enum EntityTags {
+    Any,
+    List(Vec<EntityTag>),
+}
+
+impl EntityTags {
+    fn contains(&self, etag: Option<&EntityTag>, eq: fn(&EntityTag, &EntityTag) -> bool) -> bool {
+        match self {
+            Self::Any => etag.is_some(),
+            Self::List(entity_tags) => {
+                etag.is_some_and(|etag| entity_tags.iter().any(|entity_tag| eq(entity_tag, etag)))
+            }
+        }
+    }
+}
+
+fn entity_tags(request_headers: &Headers, header_name: &HeaderName) -> Option<EntityTags> {
+    let header_value = request_headers.get_combined(header_name)?.to_string();
+    if header_value.trim() == "*" {
+        return Some(EntityTags::Any);
+    }
+
+    // Opaque tags may contain commas, so the list can't just be split on them
+    let mut entity_tags = Vec::new();
+    let mut remainder = header_value.as_str();
+    loop {
+        remainder = remainder.trim_start_matches([' ', '\t', ',']);
+        if remainder.is_empty() {
+            break;
+        }
+        let prefix_length = if remainder.starts_with("W/") { 3 } else { 1 };
+        let length = remainder.get(prefix_length..)?.find('"')? + prefix_length + 1;
+        entity_tags.push(EntityTag::parse(&remainder[..length])?);
+        remainder = &remainder[length..];
+        let is_separated = matches!(
+            remainder.trim_start_matches([' ', '\t']).bytes().next(),
+            None | Some(b',')
+        );
+        if !is_separated {
+            return None;
+        }
+    }
+    Some(EntityTags::List(entity_tags))
+}
+
+fn date(header_value: Option<&HeaderValue>) -> Option<SystemTime> {
+    http_date::parse(&header_value?.to_string())
+}
+
+// HTTP dates are only precise to the second, so a file modified within the second a client
+// was sent can't be newer than it
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "Sun, 06 Nov 1994 08:49:37 GMT"
+    const TIME: u64 = 784_111_777;
+
+    fn time(offset: i64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(TIME.checked_add_signed(offset).unwrap())
+    }
+
+    fn headers(headers: &[(&str, &str)]) -> Headers {
+        headers
+            .iter()
+            .map(|(name, value)| (HeaderName::new(name), HeaderValue::new(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn parse_entity_tags() {
+        assert_eq!(EntityTag::parse("\"abc\""), Some(EntityTag::strong("abc")));
+        assert_eq!(EntityTag::parse("W/\"a,b\""), Some(EntityTag::weak("a,b")));
+        assert_eq!(EntityTag::parse("\"\""), Some(EntityTag::strong("")));
+        for entity_tag in ["abc", "\"abc", "w/\"abc\"", "\"a\"b\"", "\"a b\"", "\""] {
+            assert_eq!(EntityTag::parse(entity_tag), None, "Parsing {entity_tag}");
+        }
+        assert_eq!(EntityTag::weak("abc").to_string(), "W/\"abc\"");
+    }
+
+    #[test]
+    fn compare_entity_tags() {
+        let strong = EntityTag::strong("1");
+        let weak = EntityTag::weak("1");
+
+        assert!(strong.strong_eq(&EntityTag::strong("1")));
+        assert!(!strong.strong_eq(&weak));
+        assert!(!weak.strong_eq(&weak));
+        assert!(strong.weak_eq(&weak));
+        assert!(weak.weak_eq(&weak));
+        assert!(!strong.weak_eq(&EntityTag::strong("2")));
+    }
+
+    #[test]
+    fn evaluate_preconditions() {
+        let etag = EntityTag::strong("v2");
+        let last_modified = time(0) + Duration::from_millis(500);
+
+        for (request_headers, expected) in [
+            (vec![], Precondition::Passed),
+            (vec![("If-Match", "\"v1\", \"v2\"")], Precondition::Passed),
+            (vec![("If-Match", "*")], Precondition::Passed),
+            (vec![("If-Match", "\"v1\"")], Precondition::Failed),
+            (vec![("If-Match", "W/\"v2\"")], Precondition::Failed),
+            (
+                vec![("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")],
+                Precondition::Passed,
+            ),
+            (
+                vec![("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")],
+                Precondition::Failed,
+            ),
+            // If-Match takes precedence over If-Unmodified-Since
+            (
+                vec![
+                    ("If-Match", "\"v2\""),
+                    ("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT"),
+                ],
+                Precondition::Passed,
+            ),
+            (
+                vec![("If-None-Match", "W/\"v2\"")],
+                Precondition::NotModified,
+            ),
+            (vec![("If-None-Match", "\"v1\"")], Precondition::Passed),
+            (vec![("If-None-Match", "*")], Precondition::NotModified),
+            (
+                vec![("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")],
+                Precondition::NotModified,
+            ),
+            (
+                vec![("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")],
+                Precondition::Passed,
+            ),
+            // If-None-Match takes precedence over If-Modified-Since
+            (
+                vec![
+                    ("If-None-Match", "\"v1\""),
+                    ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ],
+                Precondition::Passed,
+            ),
+            // Headers that can't be parsed are ignored
+            (vec![("If-Match", "v1")], Precondition::Passed),
+            (
+                vec![("If-Modified-Since", "yesterday")],
+                Precondition::Passed,
+            ),
+        ] {
+            let precondition = evaluate(
+                &Method::Get,
+                &headers(&request_headers),
+                Some(&etag),
+                Some(last_modified),
+            );
+
+            assert_eq!(precondition, expected, "Evaluating {request_headers:?}");
+        }
+    }
+
+    #[test]
+    fn unsafe_methods_fail_instead_of_not_modified() {
+        let etag = EntityTag::strong("v2");
+        let if_none_match = headers(&[("If-None-Match", "\"v2\"")]);
+        let if_modified_since = headers(&[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+
+        assert_eq!(
+            evaluate(&Method::Put, &if_none_match, Some(&etag), Some(time(0))),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(&Method::Put, &if_modified_since, Some(&etag), Some(time(0))),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(&Method::Head, &if_none_match, Some(&etag), Some(time(0))),
+            Precondition::NotModified
+        );
+    }
+
+    #[test]
+    fn missing_validators_fail_if_match() {
+        let if_match = headers(&[("If-Match", "*")]);
+        let if_none_match = headers(&[("If-None-Match", "*")]);
+
+        assert_eq!(
+            evaluate(&Method::Put, &if_match, None, None),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(&Method::Put, &if_none_match, None, None),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn if_range_matches_strong_tags_and_exact_dates() {
+        let etag = EntityTag::strong("v2");
+        let last_modified = Some(time(0));
+
+        for (if_range, expected) in [
+            (None, true),
+            (Some("\"v2\""), true),
+            (Some("W/\"v2\""), false),
+            (Some("\"v1\""), false),
+            (Some("Sun, 06 Nov 1994 08:49:37 GMT"), true),
+            (Some("Sun, 06 Nov 1994 08:49:38 GMT"), false),
+            (Some("yesterday"), false),
+        ] {
+            let if_range = if_range.map(HeaderValue::new);
+
+            assert_eq!(
+                is_if_range_fresh(if_range.as_ref(), Some(&etag), last_modified),
+                expected,
+                "If-Range: {if_range:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,645 @@
+use std::{io, time::Duration};
+
+use bytes::{Buf, BytesMut};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time,
+};
+
+use crate::{
+    body::{Body, BodyStream},
+    chunked,
+    error::Error,
+    limits::ParserLimits,
+    method::Method,
+    request::Request,
+    response::Response,
+    version::Version,
+};
+
+const INITIAL_BUFFER_CAPACITY: usize = 4 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectionError {
+    #[error("connection reset by peer with a partial request in the buffer")]
+    ConnectionReset,
+    #[error("received an invalid HTTP request: {0}")]
+    InvalidRequest(#[from] Error),
+    #[error("timed out waiting for the rest of a partial request")]
+    TimedOut,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Keep-Alive
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct KeepAlive {
+    // How long an idle connection is kept open waiting for the next request
+    pub timeout: Duration,
+    // The maximum number of requests served on a single connection
+    pub max: usize,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max: 1000,
+        }
+    }
+}
+
+// Buffers bytes read from `stream` until they contain a complete request, so requests
+// split across several TCP segments, or larger than a single read, are parsed correctly.
+#[derive(Debug)]
+pub struct Connection<S> {
+    stream: S,
+    buffer: BytesMut,
+    keep_alive: KeepAlive,
+    limits: ParserLimits,
+    requests_read: usize,
+    is_persistent: bool,
+    // Of the last request read, which its response is written for
+    version: Version,
+    method: Method,
+}
+
+impl<S> Connection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(stream: S) -> Self {
+        Self::with_keep_alive(stream, KeepAlive::default())
+    }
+
+    pub fn with_keep_alive(stream: S, keep_alive: KeepAlive) -> Self {
+        Self {
+            stream,
+            buffer: BytesMut::with_capacity(INITIAL_BUFFER_CAPACITY),
+            keep_alive,
+            limits: ParserLimits::default(),
+            requests_read: 0,
+            is_persistent: true,
+            version: Version::default(),
+            method: Method::default(),
+        }
+    }
+
+    // Requests exceeding `limits` are read as errors, so the buffer never grows much past them
+    pub fn set_limits(mut self, limits: ParserLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reads the next request from the stream.
+    ///
+    /// Returns `Ok(None)` if the peer closed the connection, or left it idle for longer than
+    /// the keep-alive timeout, between requests.
+    pub async fn read_request(&mut self) -> Result<Option<Request>, ConnectionError> {
+        loop {
+            match self.parse_request() {
+                Ok(Some(request)) => {
+                    self.requests_read += 1;
+                    self.version = request.version();
+                    self.method = request.method();
+                    self.is_persistent =
+                        request.is_persistent() && self.requests_read < self.keep_alive.max;
+                    return Ok(Some(request));
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    self.is_persistent = false;
+                    // Errors are explained in the body of their response
+                    self.method = Method::default();
+                    return Err(error);
+                }
+            }
+
+            let read = time::timeout(
+                self.keep_alive.timeout,
+                self.stream.read_buf(&mut self.buffer),
+            );
+            let number_of_bytes = match read.await {
+                Ok(result) => result?,
+                Err(_) if self.buffer.is_empty() => return Ok(None),
+                Err(_) => return Err(ConnectionError::TimedOut),
+            };
+
+            if number_of_bytes == 0 {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(ConnectionError::ConnectionReset)
+                };
+            }
+        }
+    }
+
+    /// Whether the connection should be kept open after responding to the last request read.
+    pub fn is_persistent(&self) -> bool {
+        self.is_persistent
+    }
+
+    /// Writes `response`, adding the `Connection` and `Keep-Alive` headers that tell the client
+    /// whether it may send further requests on this connection.
+    ///
+    /// Streamed bodies of unknown length are sent chunked to HTTP/1.1 clients. Older clients
+    /// don't understand chunked encoding, so the body is instead delimited by closing the
+    /// connection.
+    ///
+    /// Responses to HEAD requests are written with all of their headers but none of their body,
+    /// so handlers can respond to them as they would to GET. Streamed bodies aren't read at all.
+    pub async fn write_response(&mut self, response: Response) -> io::Result<()> {
+        let body_stream = match response.body() {
+            Some(Body::Stream(body_stream)) => Some(body_stream.clone()),
+            _ => None,
+        };
+        let is_chunked = match &body_stream {
+            Some(body_stream) if body_stream.length().is_none() => {
+                if self.version < Version::OnePointOne {
+                    self.is_persistent = false;
+                }
+                self.version >= Version::OnePointOne
+            }
+            _ => false,
+        };
+
+        let response = if is_chunked {
+            response.set_transfer_encoding("chunked")
+        } else {
+            response
+        };
+        let response = if self.is_persistent {
+            let remaining_requests = self.keep_alive.max - self.requests_read;
+            let keep_alive = format!(
+                "timeout={}, max={remaining_requests}",
+                self.keep_alive.timeout.as_secs()
+            );
+            response
+                .set_connection("keep-alive")
+                .set_keep_alive(keep_alive)
+        } else {
+            response.set_connection("close")
+        };
+
+        // https://www.rfc-editor.org/rfc/rfc9110#section-9.3.2
+        let is_head = self.method == Method::Head;
+        let mut buffer = BytesMut::new();
+        if is_head {
+            response.encode_head(&mut buffer);
+        } else {
+            response.encode(&mut buffer);
+        }
+        self.stream.write_all(&buffer).await?;
+        if let Some(body_stream) = body_stream.filter(|_| !is_head) {
+            // A partially written body leaves the client unable to find the next response
+            if let Err(error) = self.write_body_stream(&body_stream, is_chunked).await {
+                self.is_persistent = false;
+                return Err(error);
+            }
+        }
+        self.stream.flush().await
+    }
+
+    async fn write_body_stream(
+        &mut self,
+        body_stream: &BodyStream,
+        is_chunked: bool,
+    ) -> io::Result<()> {
+        let mut source = body_stream.take_source().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The body stream has already been consumed",
+            )
+        })?;
+
+        if is_chunked {
+            let mut buffer = BytesMut::new();
+            while let Some(chunk) = source.next_chunk().await? {
+                chunked::encode_chunk(&chunk, &mut buffer);
+                self.stream.write_all(&buffer).await?;
+                buffer.clear();
+            }
+            chunked::encode_last_chunk(source.trailers(), &mut buffer);
+            return self.stream.write_all(&buffer).await;
+        }
+
+        let mut remaining = body_stream.length();
+        while remaining != Some(0) {
+            let Some(chunk) = source.next_chunk().await? else {
+                return match remaining {
+                    None => Ok(()),
+                    Some(_) => Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The body stream ended before its declared length",
+                    )),
+                };
+            };
+            // Never write more than the `Content-Length` promised to the client
+            let chunk = match remaining {
+                None => &chunk[..],
+                Some(length) => &chunk[..chunk.len().min(length)],
+            };
+            self.stream.write_all(chunk).await?;
+            remaining = remaining.map(|length| length - chunk.len());
+        }
+        Ok(())
+    }
+
+    fn parse_request(&mut self) -> Result<Option<Request>, ConnectionError> {
+        match Request::parse_with_limits(&self.buffer, &self.limits) {
+            Ok((remainder, request)) => {
+                let consumed = self.buffer.len() - remainder.len();
+                self.buffer.advance(consumed);
+                Ok(Some(request))
+            }
+            Err(nom::Err::Incomplete(_)) => Ok(None),
+            Err(nom::Err::Error(error) | nom::Err::Failure(error)) => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{self, DuplexStream};
+    use tokio_test::io::Builder;
+
+    use crate::{headers::Headers, method::Method};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_request_split_across_reads() {
+        let stream = Builder::new()
+            .read(b"GET /echo/abc HT")
+            .read(b"TP/1.1\r\nHost: local")
+            .read(b"host:4221\r\n")
+            .read(b"\r\n")
+            .build();
+        let mut connection = Connection::new(stream);
+
+        let request = connection
+            .read_request()
+            .await
+            .expect("Request is valid")
+            .expect("Request is complete");
+
+        assert_eq!(request.method(), Method::Get);
+        assert_eq!(request.raw_target(), "/echo/abc");
+        assert!(connection.read_request().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn read_body_split_across_reads() {
+        let stream = Builder::new()
+            .read(b"POST /files/a HTTP/1.1\r\nContent-Length: 6\r\n\r\nab")
+            .read(b"cd")
+            .read(b"efGET / HTTP/1.1\r\n\r\n")
+            .build();
+        let mut connection = Connection::new(stream);
+
+        let first_request = connection.read_request().await.unwrap().unwrap();
+        let second_request = connection.read_request().await.unwrap().unwrap();
+
+        assert_eq!(first_request.body(), Some(&Body::from(b"abcdef".to_vec())));
+        assert_eq!(second_request.raw_target(), "/");
+    }
+
+    #[tokio::test]
+    async fn read_request_larger_than_initial_buffer() {
+        let user_agent = "a".repeat(INITIAL_BUFFER_CAPACITY * 2);
+        let bytes = format!("GET /user-agent HTTP/1.1\r\nUser-Agent: {user_agent}\r\n\r\n");
+        let stream = Builder::new().read(bytes.as_bytes()).build();
+        let mut connection = Connection::new(stream);
+
+        let request = connection.read_request().await.unwrap().unwrap();
+
+        assert_eq!(
+            request.headers().user_agent().map(ToString::to_string),
+            Some(user_agent)
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_request_is_connection_reset() {
+        let stream = Builder::new().read(b"GET / HTTP/1.1\r\n").build();
+        let mut connection = Connection::new(stream);
+
+        let result = connection.read_request().await;
+
+        assert!(matches!(result, Err(ConnectionError::ConnectionReset)));
+    }
+
+    #[tokio::test]
+    async fn malformed_request() {
+        let stream = Builder::new().read(b"BREW / HTCPCP/1.0\r\n\r\n").build();
+        let mut connection = Connection::new(stream);
+
+        let result = connection.read_request().await;
+
+        assert!(matches!(
+            result,
+            Err(ConnectionError::InvalidRequest(Error::UnknownMethod))
+        ));
+    }
+
+    #[tokio::test]
+    async fn oversized_request_is_rejected_before_it_is_fully_read() {
+        let (mut client, server) = io::duplex(1024);
+        let limits = ParserLimits {
+            max_body_size: 4,
+            ..ParserLimits::default()
+        };
+        let mut connection = Connection::new(server).set_limits(limits);
+
+        client
+            .write_all(b"POST /files/a HTTP/1.1\r\nContent-Length: 1000000\r\n\r\n")
+            .await
+            .unwrap();
+        let result = connection.read_request().await;
+
+        assert!(matches!(
+            result,
+            Err(ConnectionError::InvalidRequest(Error::BodyTooLarge))
+        ));
+        assert!(!connection.is_persistent());
+    }
+
+    async fn read_response_head(client: &mut DuplexStream) -> String {
+        let mut buffer = vec![0u8; 1024];
+        let number_of_bytes = client.read(&mut buffer).await.unwrap();
+        String::from_utf8(buffer[..number_of_bytes].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn connection_close_is_echoed() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        connection.read_request().await.unwrap().unwrap();
+        connection
+            .write_response(Response::ok().build())
+            .await
+            .unwrap();
+
+        assert!(!connection.is_persistent());
+        assert!(read_response_head(&mut client)
+            .await
+            .contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn persistent_connection_advertises_keep_alive() {
+        let (mut client, server) = io::duplex(1024);
+        let keep_alive = KeepAlive {
+            timeout: Duration::from_secs(7),
+            max: 3,
+        };
+        let mut connection = Connection::with_keep_alive(server, keep_alive);
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+        connection.read_request().await.unwrap().unwrap();
+        connection
+            .write_response(Response::ok().build())
+            .await
+            .unwrap();
+
+        let response = read_response_head(&mut client).await;
+        assert!(connection.is_persistent());
+        assert!(response.contains("Connection: keep-alive\r\n"));
+        assert!(response.contains("Keep-Alive: timeout=7, max=2\r\n"));
+    }
+
+    #[tokio::test]
+    async fn connection_closes_after_max_requests() {
+        let stream = Builder::new()
+            .read(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n")
+            .build();
+        let keep_alive = KeepAlive {
+            max: 2,
+            ..KeepAlive::default()
+        };
+        let mut connection = Connection::with_keep_alive(stream, keep_alive);
+
+        connection.read_request().await.unwrap().unwrap();
+        assert!(connection.is_persistent());
+        connection.read_request().await.unwrap().unwrap();
+        assert!(!connection.is_persistent());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connection_times_out() {
+        let (_client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+
+        let request = connection.read_request().await.unwrap();
+
+        assert!(request.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_partial_request_times_out() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+        let result = connection.read_request().await;
+
+        assert!(matches!(result, Err(ConnectionError::TimedOut)));
+    }
+
+    async fn read_to_string(client: &mut DuplexStream) -> String {
+        let mut bytes = Vec::new();
+        client.read_to_end(&mut bytes).await.unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stream_of_unknown_length_is_chunked() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let (sender, body_stream) = BodyStream::channel();
+        tokio::spawn(async move {
+            sender.send("Hello, ").await.unwrap();
+            sender.send("World!").await.unwrap();
+            sender.finish().await.unwrap();
+        });
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let response = read_to_string(&mut client).await;
+        assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!response.contains("Content-Length"));
+        assert!(response.ends_with("\r\n\r\n7\r\nHello, \r\n6\r\nWorld!\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn stream_cut_short_closes_connection_without_last_chunk() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let (sender, body_stream) = BodyStream::channel();
+        tokio::spawn(async move {
+            sender.send("Hello, ").await.unwrap();
+        });
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream).build();
+        let result = connection.write_response(response).await;
+
+        assert!(result.is_err());
+        assert!(!connection.is_persistent());
+        drop(connection);
+        let response = read_to_string(&mut client).await;
+        assert!(response.ends_with("\r\n\r\n7\r\nHello, \r\n"));
+    }
+
+    #[tokio::test]
+    async fn chunked_stream_sends_trailers() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let (sender, body_stream) = BodyStream::channel();
+        tokio::spawn(async move {
+            sender.send("abc").await.unwrap();
+            let trailers = Headers::default().set_content_type("text/plain");
+            sender.send_trailers(trailers).await.unwrap();
+        });
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let response = read_to_string(&mut client).await;
+        assert!(response.ends_with("3\r\nabc\r\n0\r\nContent-Type: text/plain\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn stream_of_known_length_is_not_chunked() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let body_stream = BodyStream::from_reader(&b"Hello, World!"[..]).set_length(5);
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let response = read_to_string(&mut client).await;
+        assert!(response.contains("Content-Length: 5\r\n"));
+        assert!(!response.contains("Transfer-Encoding"));
+        assert!(response.ends_with("\r\n\r\nHello"));
+    }
+
+    #[tokio::test]
+    async fn stream_to_http_1_0_client_is_close_delimited() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+            .await
+            .unwrap();
+        let body_stream = BodyStream::from_reader(&b"Hello, World!"[..]);
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream).build();
+        connection.write_response(response).await.unwrap();
+
+        assert!(!connection.is_persistent());
+        drop(connection);
+        let response = read_to_string(&mut client).await;
+        assert!(response.contains("Connection: close\r\n"));
+        assert!(!response.contains("Transfer-Encoding"));
+        assert!(response.ends_with("\r\n\r\nHello, World!"));
+    }
+
+    #[tokio::test]
+    async fn head_response_has_headers_but_no_body() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"HEAD / HTTP/1.1\r\n\r\nHEAD / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let body_stream = BodyStream::from_reader(&b"Hello, World!"[..]).set_length(13);
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body("abc").build();
+        connection.write_response(response).await.unwrap();
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(body_stream.clone()).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let response = read_to_string(&mut client).await;
+        let mut responses = response.split_inclusive("\r\n\r\n");
+        assert!(responses.next().unwrap().contains("Content-Length: 3\r\n"));
+        assert!(responses.next().unwrap().contains("Content-Length: 13\r\n"));
+        assert_eq!(responses.next(), None);
+        assert!(body_stream.take_source().is_some());
+    }
+
+    #[tokio::test]
+    async fn error_response_after_head_has_a_body() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"HEAD / HTTP/1.1\r\n\r\nBAD\r\n\r\n")
+            .await
+            .unwrap();
+
+        connection.read_request().await.unwrap().unwrap();
+        connection
+            .write_response(Response::ok().build())
+            .await
+            .unwrap();
+        let Err(ConnectionError::InvalidRequest(error)) = connection.read_request().await else {
+            panic!("Request should be invalid");
+        };
+        let response = Response::from_error(&error).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let response = read_to_string(&mut client).await;
+        assert!(response.ends_with(&format!("\r\n\r\n{error}\n")));
+    }
+
+    #[tokio::test]
+    async fn binary_body_is_written_verbatim() {
+        let (mut client, server) = io::duplex(1024);
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let bytes = vec![0x89, b'P', b'N', b'G', 0x00, 0xff];
+
+        connection.read_request().await.unwrap().unwrap();
+        let response = Response::ok().set_body(bytes.clone()).build();
+        connection.write_response(response).await.unwrap();
+        drop(connection);
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.ends_with(&bytes));
+    }
+}
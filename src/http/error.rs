@@ -1,38 +1,71 @@
 use std::fmt;
 
-#[derive(Debug)]
-pub enum Error<'a> {
-    Parsing(&'a [u8]),
-    NotEnoughRequestParts(u8),
-    InvalidMethod(&'a str),
-    InvalidTarget(&'a str),
-    InvalidVersion(&'a str),
-    UnsupportedVersion(&'a str),
-    TooManyRequestLineParts(u8),
-    NotEnoughRequestLineParts(u8),
-    InvalidHeaderString(&'a str),
-    InvalidHeaderName(&'a str),
+use crate::status_line::Status;
+
+// Why a request couldn't be parsed. Each reason maps to the status the server responds with.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Error {
+    MalformedRequestLine,
+    UnknownMethod,
+    InvalidTarget,
+    TargetTooLong,
+    InvalidVersion,
+    UnsupportedVersion,
+    MalformedHeader,
+    HeadersTooLarge,
+    InvalidContentLength,
+    ConflictingFraming,
+    UnsupportedTransferCoding,
+    ChunkedNotFinal,
+    MalformedChunkedBody,
+    BodyTooLarge,
 }
 
-impl<'a> fmt::Display for Error<'a> {
+impl Error {
+    pub fn status(&self) -> Status {
+        match self {
+            Self::MalformedRequestLine
+            | Self::InvalidTarget
+            | Self::InvalidVersion
+            | Self::MalformedHeader
+            | Self::InvalidContentLength
+            | Self::ConflictingFraming
+            | Self::ChunkedNotFinal
+            | Self::MalformedChunkedBody => Status::BadRequest,
+            Self::UnknownMethod | Self::UnsupportedTransferCoding => Status::NotImplemented,
+            Self::TargetTooLong => Status::UriTooLong,
+            Self::UnsupportedVersion => Status::HttpVersionNotSupported,
+            Self::HeadersTooLarge => Status::RequestHeaderFieldsTooLarge,
+            Self::BodyTooLarge => Status::ContentTooLarge,
+        }
+    }
+}
+
+impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Parsing(bytes) => write!(f, "Unable to parse &[u8] into &str: {bytes:?}"),
-            Self::NotEnoughRequestParts(number_of_parts) => write!(
+            Self::MalformedRequestLine => write!(
                 f,
-                "Expecting request to have at least 2 parts (request line, zero or more headers ), but received {number_of_parts}"
+                "Expecting request line to have 3 parts (HTTP method, request target, and HTTP version) separated by spaces"
             ),
-            Self::InvalidMethod(method) => write!(f, "{method} is not a valid HTTP method"),
-            Self::InvalidTarget(target) => write!(f, "{target} is not a valid target"),
-            Self::InvalidVersion(version) => write!(f, "{version} is not a valid HTTP version"),
-            Self::UnsupportedVersion(version) => write!(f, "{version} is not supported"),
-            Self::TooManyRequestLineParts(number_of_parts)
-            | Self::NotEnoughRequestLineParts(number_of_parts) => write!(
+            Self::UnknownMethod => write!(f, "Request method is not supported"),
+            Self::InvalidTarget => write!(f, "Request target is not valid"),
+            Self::TargetTooLong => write!(f, "Request target is too long"),
+            Self::InvalidVersion => write!(f, "HTTP version is not valid"),
+            Self::UnsupportedVersion => write!(f, "HTTP version is not supported"),
+            Self::MalformedHeader => write!(f, "Header is not a valid header name - header value pair"),
+            Self::HeadersTooLarge => write!(f, "Request headers are too large"),
+            Self::InvalidContentLength => write!(f, "Content-Length is not a single non-negative integer"),
+            Self::ConflictingFraming => write!(
                 f,
-                "Expecting request line to have 3 parts (HTTP method, request target, and HTTP version), but received {number_of_parts}"
+                "Request must not have both Content-Length and Transfer-Encoding headers"
             ),
-            Self::InvalidHeaderString(key_value_pair) => write!(f, "{key_value_pair} is not a valid header name - header value pair because it does not contain a ':' "),
-            Self::InvalidHeaderName(header_name) => write!(f, "{header_name} is not a valid header name")
+            Self::UnsupportedTransferCoding => write!(f, "Transfer-Encoding is not supported"),
+            Self::ChunkedNotFinal => write!(f, "Chunked must be the final transfer coding"),
+            Self::MalformedChunkedBody => write!(f, "Chunked request body is not valid"),
+            Self::BodyTooLarge => write!(f, "Request body is too large"),
         }
     }
 }
+
+impl std::error::Error for Error {}
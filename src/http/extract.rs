@@ -0,0 +1,313 @@
+use std::{
+    any::{self, Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+use crate::{
+    body::Body,
+    headers::Headers,
+    method::Method,
+    path::{self, Path},
+    request::Request,
+    router::Params,
+    status_line::Status,
+};
+
+const FORM_MEDIA_TYPE: &str = "application/x-www-form-urlencoded";
+
+// A value a handler takes as an argument, extracted from the request it handles. Requests are read
+// in full before they're handled, so extraction never has to wait for the body.
+pub trait FromRequest: Sized {
+    fn from_request(request: &Request, state: &SharedState) -> Result<Self, Rejection>;
+}
+
+impl FromRequest for Method {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        Ok(request.method())
+    }
+}
+
+impl FromRequest for Headers {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        Ok(request.headers().clone())
+    }
+}
+
+// Requests whose targets have no path, like `CONNECT` and `OPTIONS *`, are rejected, which
+// handlers can avoid by taking an `Option<Path>`
+impl FromRequest for Path {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        request.path().cloned().ok_or(Rejection::MissingPath)
+    }
+}
+
+impl FromRequest for Params {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        Ok(request.params().clone())
+    }
+}
+
+// Requests without a body have empty content
+impl FromRequest for Vec<u8> {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        let bytes = request.body().map(Body::as_bytes).unwrap_or_default();
+        Ok(bytes.to_vec())
+    }
+}
+
+impl FromRequest for String {
+    fn from_request(request: &Request, state: &SharedState) -> Result<Self, Rejection> {
+        let bytes = Vec::from_request(request, state)?;
+        String::from_utf8(bytes).map_err(|_| Rejection::InvalidUtf8Body)
+    }
+}
+
+// Unlike `Vec<u8>` and `String`, requires the request to have a body
+impl FromRequest for Body {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        request.body().cloned().ok_or(Rejection::MissingBody)
+    }
+}
+
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(request: &Request, state: &SharedState) -> Result<Self, Rejection> {
+        Ok(T::from_request(request, state).ok())
+    }
+}
+
+// The decoded name - value pairs of the request's query
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Query(pub Vec<(String, String)>);
+
+impl Query {
+    // Returns the first value of `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        find_pair(&self.0, name)
+    }
+}
+
+impl FromRequest for Query {
+    fn from_request(request: &Request, _state: &SharedState) -> Result<Self, Rejection> {
+        let pairs = request
+            .path()
+            .map(|path| path.query_pairs().collect())
+            .unwrap_or_default();
+        Ok(Self(pairs))
+    }
+}
+
+// The decoded name - value pairs of a body of type "application/x-www-form-urlencoded"
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Form(pub Vec<(String, String)>);
+
+impl Form {
+    // Returns the first value of `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        find_pair(&self.0, name)
+    }
+}
+
+impl FromRequest for Form {
+    fn from_request(request: &Request, state: &SharedState) -> Result<Self, Rejection> {
+        let is_form = request
+            .headers()
+            .content_type()
+            .is_some_and(|content_type| {
+                let content_type = content_type.to_string();
+                let media_type = content_type.split(';').next().unwrap_or_default();
+                media_type.trim().eq_ignore_ascii_case(FORM_MEDIA_TYPE)
+            });
+        if !is_form {
+            return Err(Rejection::UnsupportedMediaType(FORM_MEDIA_TYPE));
+        }
+        let body = String::from_request(request, state)?;
+        Ok(Self(path::parse_urlencoded(&body).collect()))
+    }
+}
+
+// A value shared by every handler of a `Router`, added with `Router::set_state`
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct State<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(_request: &Request, state: &SharedState) -> Result<Self, Rejection> {
+        state
+            .get::<T>()
+            .cloned()
+            .map(Self)
+            .ok_or(Rejection::MissingState(any::type_name::<T>()))
+    }
+}
+
+// The values of each type shared by a `Router`'s handlers
+#[derive(Clone, Default)]
+pub struct SharedState(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl SharedState {
+    // Replaces any existing value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.0.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+}
+
+impl fmt::Debug for SharedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedState").finish_non_exhaustive()
+    }
+}
+
+// Why a value couldn't be extracted from a request, which is responded to instead of calling the
+// handler
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Rejection {
+    MissingPath,
+    MissingBody,
+    InvalidUtf8Body,
+    // Holds the media type the body should have
+    UnsupportedMediaType(&'static str),
+    // Holds the name of the missing type, which the router should have been given
+    MissingState(&'static str),
+}
+
+impl Rejection {
+    pub fn status(&self) -> Status {
+        match self {
+            Self::MissingPath | Self::MissingBody | Self::InvalidUtf8Body => Status::BadRequest,
+            Self::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            Self::MissingState(_) => Status::InternalServerError,
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPath => write!(f, "Request target should have a path"),
+            Self::MissingBody => write!(f, "Request should have a body"),
+            Self::InvalidUtf8Body => write!(f, "Request body is not valid UTF-8"),
+            Self::UnsupportedMediaType(media_type) => {
+                write!(f, "Request body should be of type {media_type}")
+            }
+            Self::MissingState(type_name) => {
+                write!(f, "Router has no shared state of type {type_name}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+fn find_pair<'a>(pairs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(pair_name, _)| pair_name == name)
+        .map(|(_, value)| value.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        headers::{HeaderName, HeaderValue},
+        request_target::RequestTarget,
+        version::Version,
+    };
+
+    use super::*;
+
+    fn request(target: &str, headers: &[(HeaderName, &str)], body: Option<&[u8]>) -> Request {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::new(*value)))
+            .collect();
+        Request::new(
+            Method::Post,
+            RequestTarget::Origin(Path::new(target)),
+            Version::default(),
+            headers,
+            body.map(|body| Body::from(body.to_vec())),
+        )
+    }
+
+    #[test]
+    fn extract_body() {
+        let state = SharedState::default();
+        let with_body = request("/", &[], Some(b"abc"));
+        let without_body = request("/", &[], None);
+        let invalid_utf8 = request("/", &[], Some(b"\xff"));
+
+        assert_eq!(String::from_request(&with_body, &state).unwrap(), "abc");
+        assert_eq!(Vec::from_request(&without_body, &state).unwrap(), b"");
+        assert_eq!(
+            String::from_request(&invalid_utf8, &state),
+            Err(Rejection::InvalidUtf8Body)
+        );
+        assert_eq!(
+            Body::from_request(&without_body, &state),
+            Err(Rejection::MissingBody)
+        );
+        assert_eq!(
+            Option::<Body>::from_request(&without_body, &state),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn extract_query_and_form() {
+        let state = SharedState::default();
+        let form = request(
+            "/search?q=a+b&page=2",
+            &[(
+                HeaderName::ContentType,
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )],
+            Some(b"name=J%C3%BCrgen&empty="),
+        );
+
+        let query = Query::from_request(&form, &state).unwrap();
+        let form = Form::from_request(&form, &state).unwrap();
+
+        assert_eq!(query.get("q"), Some("a b"));
+        assert_eq!(query.get("page"), Some("2"));
+        assert_eq!(form.get("name"), Some("Jürgen"));
+        assert_eq!(form.get("empty"), Some(""));
+        assert_eq!(form.get("missing"), None);
+    }
+
+    #[test]
+    fn forms_must_have_the_form_type() {
+        let state = SharedState::default();
+        let json = request(
+            "/",
+            &[(HeaderName::ContentType, "application/json")],
+            Some(b"{}"),
+        );
+
+        let rejection = Form::from_request(&json, &state).unwrap_err();
+
+        assert_eq!(rejection.status(), Status::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn extract_state_by_type() {
+        let mut state = SharedState::default();
+        state.insert(42_u32);
+        state.insert("name".to_string());
+        let request = request("/", &[], None);
+
+        assert_eq!(State::<u32>::from_request(&request, &state), Ok(State(42)));
+        assert_eq!(
+            State::<String>::from_request(&request, &state),
+            Ok(State("name".to_string()))
+        );
+        assert_eq!(
+            State::<u64>::from_request(&request, &state),
+            Err(Rejection::MissingState("u64"))
+        );
+    }
+}
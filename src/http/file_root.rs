@@ -0,0 +1,212 @@
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::status_line::Status;
+
+// A directory that files are served from and written to, resolving requested paths so that
+// they can't escape it, whether through ".." segments, absolute paths or symlinks
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FileRoot {
+    root: PathBuf,
+    allows_symlinks_outside_root: bool,
+}
+
+impl FileRoot {
+    // Fails if `root` doesn't exist
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        let file_root = Self {
+            root,
+            allows_symlinks_outside_root: false,
+        };
+        Ok(file_root)
+    }
+
+    // Whether symlinks inside the root may point to files outside it, which they can't by default
+    pub fn set_allows_symlinks_outside_root(mut self, allows_symlinks_outside_root: bool) -> Self {
+        self.allows_symlinks_outside_root = allows_symlinks_outside_root;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Resolves `requested`, relative to the root, to an existing file or directory
+    pub fn resolve(&self, requested: &str) -> Result<PathBuf, FileRootError> {
+        let path = self.join(requested)?;
+        let canonical_path = fs::canonicalize(path).map_err(FileRootError::from)?;
+        self.check_inside_root(canonical_path)
+    }
+
+    // Resolves `requested`, relative to the root, to a path a new file can be created at. Its
+    // parent directory must already exist.
+    pub fn resolve_new(&self, requested: &str) -> Result<PathBuf, FileRootError> {
+        let path = self.join(requested)?;
+        let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return Err(FileRootError::Forbidden);
+        };
+        let canonical_parent = fs::canonicalize(parent).map_err(FileRootError::from)?;
+        let canonical_parent = self.check_inside_root(canonical_parent)?;
+        Ok(canonical_parent.join(file_name))
+    }
+
+    // Only plain file names are joined, so the path can only go down from the root
+    fn join(&self, requested: &str) -> Result<PathBuf, FileRootError> {
+        let mut path = self.root.clone();
+        for component in Path::new(requested).components() {
+            match component {
+                Component::Normal(name) => path.push(name),
+                Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(FileRootError::Forbidden);
+                }
+            }
+        }
+        Ok(path)
+    }
+
+    // Canonical paths have had their symlinks resolved, so this catches symlinks out of the root
+    fn check_inside_root(&self, canonical_path: PathBuf) -> Result<PathBuf, FileRootError> {
+        if self.allows_symlinks_outside_root || canonical_path.starts_with(&self.root) {
+            Ok(canonical_path)
+        } else {
+            Err(FileRootError::Forbidden)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FileRootError {
+    // The requested path is outside the root
+    Forbidden,
+    NotFound,
+    Io(io::Error),
+}
+
+impl FileRootError {
+    pub fn status(&self) -> Status {
+        match self {
+            Self::Forbidden => Status::Forbidden,
+            Self::NotFound => Status::NotFound,
+            Self::Io(_) => Status::InternalServerError,
+        }
+    }
+}
+
+impl From<io::Error> for FileRootError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            _ => Self::Io(error),
+        }
+    }
+}
+
+impl fmt::Display for FileRootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forbidden => write!(f, "Requested path is outside the file root"),
+            Self::NotFound => write!(f, "Requested path does not exist"),
+            Self::Io(error) => write!(f, "Failed to resolve requested path: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FileRootError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix, process};
+
+    use super::*;
+
+    // Creates an empty directory for each test, containing "root/a.txt" and "outside.txt"
+    fn make_test_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("file-root-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("root/sub")).unwrap();
+        fs::write(directory.join("root/a.txt"), "a").unwrap();
+        fs::write(directory.join("outside.txt"), "outside").unwrap();
+        directory
+    }
+
+    #[test]
+    fn resolve_files_inside_the_root() {
+        let directory = make_test_directory("inside");
+        let file_root = FileRoot::new(directory.join("root")).unwrap();
+
+        let path = file_root.resolve("./a.txt");
+
+        assert_eq!(path.unwrap(), file_root.root().join("a.txt"));
+        assert!(matches!(
+            file_root.resolve("missing.txt"),
+            Err(FileRootError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn escapes_are_forbidden() {
+        let directory = make_test_directory("escapes");
+        let file_root = FileRoot::new(directory.join("root")).unwrap();
+
+        for requested in ["../outside.txt", "/etc/passwd", "sub/../../outside.txt"] {
+            assert!(
+                matches!(file_root.resolve(requested), Err(FileRootError::Forbidden)),
+                "Resolving {requested}"
+            );
+            assert!(
+                matches!(
+                    file_root.resolve_new(requested),
+                    Err(FileRootError::Forbidden)
+                ),
+                "Resolving new {requested}"
+            );
+        }
+    }
+
+    #[test]
+    fn symlinks_outside_the_root_are_forbidden_unless_allowed() {
+        let directory = make_test_directory("symlinks");
+        unix::fs::symlink(
+            directory.join("outside.txt"),
+            directory.join("root/link.txt"),
+        )
+        .unwrap();
+        unix::fs::symlink(&directory, directory.join("root/link")).unwrap();
+        let file_root = FileRoot::new(directory.join("root")).unwrap();
+
+        assert!(matches!(
+            file_root.resolve("link.txt"),
+            Err(FileRootError::Forbidden)
+        ));
+        assert!(matches!(
+            file_root.resolve_new("link/new.txt"),
+            Err(FileRootError::Forbidden)
+        ));
+
+        let file_root = file_root.set_allows_symlinks_outside_root(true);
+
+        assert!(file_root.resolve("link.txt").is_ok());
+    }
+
+    #[test]
+    fn resolve_new_files() {
+        let directory = make_test_directory("new");
+        let file_root = FileRoot::new(directory.join("root")).unwrap();
+
+        let path = file_root.resolve_new("sub/new.txt");
+
+        assert_eq!(path.unwrap(), file_root.root().join("sub/new.txt"));
+        assert!(matches!(
+            file_root.resolve_new("missing/new.txt"),
+            Err(FileRootError::NotFound)
+        ));
+        assert!(matches!(
+            file_root.resolve_new(""),
+            Err(FileRootError::Forbidden)
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    future::{self, Future},
+    pin::Pin,
+};
+
+use crate::{
+    body::Body,
+    extract::{FromRequest, Rejection, SharedState},
+    headers::Headers,
+    request::Request,
+    response::Response,
+    response_builder::ResponseBuilder,
+    status_line::Status,
+};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// A value a handler can respond with
+pub trait IntoResponse {
+    fn into_response(self) -> ResponseBuilder;
+}
+
+impl IntoResponse for ResponseBuilder {
+    fn into_response(self) -> ResponseBuilder {
+        self
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> ResponseBuilder {
+        Response::ok().set_body(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> ResponseBuilder {
+        Response::ok().set_body(self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> ResponseBuilder {
+        Response::ok().set_body(self)
+    }
+}
+
+impl IntoResponse for Body {
+    fn into_response(self) -> ResponseBuilder {
+        Response::ok().set_body(self)
+    }
+}
+
+impl IntoResponse for Status {
+    fn into_response(self) -> ResponseBuilder {
+        Response::with_status(self)
+    }
+}
+
+// The headers replace those inferred from the body, such as `Content-Type`
+impl IntoResponse for (Status, Headers, Body) {
+    fn into_response(self) -> ResponseBuilder {
+        let (status, headers, body) = self;
+        Response::with_status(status)
+            .set_body(body)
+            .set_headers(headers)
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> ResponseBuilder {
+        match self {
+            Ok(response) => response.into_response(),
+            Err(error) => error.into_response(),
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> ResponseBuilder {
+        Response::with_status(self.status()).set_body(format!("{self}\n"))
+    }
+}
+
+// An async function or closure that handles requests for a `Router`. `Args` is the tuple of the
+// types it takes, each extracted from the request with `FromRequest`, and responding with the
+// `Rejection` if one can't be. Handlers can instead take the whole `Request`, with `Args` being
+// `Request`.
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, request: Request, state: &SharedState) -> BoxFuture<'static, ResponseBuilder>;
+}
+
+impl<F, Fut, R> Handler<Request> for F
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse,
+{
+    fn call(&self, request: Request, _state: &SharedState) -> BoxFuture<'static, ResponseBuilder> {
+        let future = self(request);
+        Box::pin(async move { future.await.into_response() })
+    }
+}
+
+macro_rules! impl_handler {
+    ($($extractor:ident),*) => {
+        impl<F, Fut, R, $($extractor),*> Handler<($($extractor,)*)> for F
+        where
+            F: Fn($($extractor),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = R> + Send + 'static,
+            R: IntoResponse,
+            $($extractor: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, request: Request, state: &SharedState) -> BoxFuture<'static, ResponseBuilder> {
+                $(
+                    let $extractor = match $extractor::from_request(&request, state) {
+                        Ok(value) => value,
+                        Err(rejection) => {
+                            return Box::pin(future::ready(rejection.into_response()));
+                        }
+                    };
+                )*
+                let future = self($($extractor),*);
+                Box::pin(async move { future.await.into_response() })
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(T1);
+impl_handler!(T1, T2);
+impl_handler!(T1, T2, T3);
+impl_handler!(T1, T2, T3, T4);
+impl_handler!(T1, T2, T3, T4, T5);
+impl_handler!(T1, T2, T3, T4, T5, T6);
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        extract::{Query, State},
+        headers::{HeaderName, HeaderValue},
+        method::Method,
+        path::Path,
+        request_target::RequestTarget,
+        version::Version,
+    };
+
+    use super::*;
+
+    fn request(target: &str) -> Request {
+        let target = RequestTarget::Origin(Path::new(target));
+        Request::new(
+            Method::Get,
+            target,
+            Version::default(),
+            Headers::default(),
+            None,
+        )
+    }
+
+    async fn call<Args>(handler: impl Handler<Args>, state: &SharedState) -> String {
+        let response = handler.call(request("/?name=World"), state).await.build();
+        response.to_string()
+    }
+
+    #[tokio::test]
+    async fn handlers_take_extractors() {
+        let mut state = SharedState::default();
+        state.insert("Hello".to_string());
+
+        async fn greet(State(greeting): State<String>, query: Query) -> String {
+            format!("{greeting}, {}!", query.get("name").unwrap_or_default())
+        }
+
+        assert_eq!(
+            call(greet, &state).await,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n\r\nHello, World!"
+        );
+        assert_eq!(
+            call(|| async { Status::NoContent }, &state).await,
+            "HTTP/1.1 204 No Content\r\n\r\n"
+        );
+        assert_eq!(
+            call(
+                |request: Request| async move { request.raw_target() },
+                &state
+            )
+            .await,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 12\r\n\r\n/?name=World"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejections_are_responded_to() {
+        let state = SharedState::default();
+
+        let response = call(|_: State<u8>| async { "unreachable" }, &state).await;
+
+        assert_eq!(
+            response,
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nContent-Length: 38\r\n\r\nRouter has no shared state of type u8\n"
+        );
+    }
+
+    #[test]
+    fn tuples_and_results_into_responses() {
+        let headers = Headers::default().set_content_type("application/json");
+        let response = (Status::Created, headers, Body::from("{}"))
+            .into_response()
+            .build();
+
+        assert_eq!(response.status(), &Status::Created);
+        assert_eq!(
+            response.headers().get(&HeaderName::ContentType),
+            Some(&HeaderValue::new("application/json"))
+        );
+
+        let result: Result<String, Status> = Err(Status::Conflict);
+        assert_eq!(result.into_response().build().status(), &Status::Conflict);
+    }
+}
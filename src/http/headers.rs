@@ -1,14 +1,16 @@
-use std::{collections::HashMap, fmt, str};
+use std::{fmt, slice, str, vec};
 
-use nom::{branch, bytes::complete, combinator, multi, sequence::Tuple, IResult};
+use nom::{bytes::streaming, combinator, multi, sequence::Tuple, IResult};
 
 use crate::parsing_utils;
 
+// Headers in the order they were received or set. A name may appear more than once, as with
+// `Set-Cookie`, or a list split across several lines.
 #[derive(Clone, Eq, PartialEq, Debug)]
-pub struct Headers(HashMap<HeaderName, HeaderValue>);
+pub struct Headers(Vec<(HeaderName, HeaderValue)>);
 
 impl Headers {
-    pub fn new(headers: HashMap<HeaderName, HeaderValue>) -> Self {
+    pub fn new(headers: Vec<(HeaderName, HeaderValue)>) -> Self {
         Self(headers)
     }
 
@@ -20,8 +22,98 @@ impl Headers {
         Ok((remainder, headers))
     }
 
+    // Returns the first value of `header_name`
     pub fn get(&self, header_name: &HeaderName) -> Option<&HeaderValue> {
-        self.0.get(header_name)
+        self.0
+            .iter()
+            .find(|(name, _)| name == header_name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_all<'a>(
+        &'a self,
+        header_name: &'a HeaderName,
+    ) -> impl Iterator<Item = &'a HeaderValue> + 'a {
+        self.0
+            .iter()
+            .filter(move |(name, _)| name == header_name)
+            .map(|(_, value)| value)
+    }
+
+    // Joins every value of a list-based header into one, as a recipient is allowed to do for
+    // any header other than `Set-Cookie`, which must be read with `get_all`
+    // https://www.rfc-editor.org/rfc/rfc9110#section-5.3
+    pub fn get_combined(&self, header_name: &HeaderName) -> Option<HeaderValue> {
+        if *header_name == HeaderName::SetCookie {
+            return self.get(header_name).cloned();
+        }
+        let values: Vec<String> = self.get_all(header_name).map(ToString::to_string).collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(HeaderValue::new(values.join(", ")))
+        }
+    }
+
+    // Replaces any existing values of `header_name`, keeping the position of the first
+    pub fn insert(&mut self, header_name: HeaderName, header_value: HeaderValue) {
+        match self.0.iter().position(|(name, _)| *name == header_name) {
+            None => self.0.push((header_name, header_value)),
+            Some(index) => {
+                self.0[index].1 = header_value;
+                let mut position = 0;
+                self.0.retain(|(name, _)| {
+                    let is_duplicate = position > index && *name == header_name;
+                    position += 1;
+                    !is_duplicate
+                });
+            }
+        }
+    }
+
+    // Removes every value of `header_name`, returning the first
+    pub fn remove(&mut self, header_name: &HeaderName) -> Option<HeaderValue> {
+        let mut removed = None;
+        self.0.retain(|(name, value)| {
+            if name != header_name {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(value.clone());
+            }
+            false
+        });
+        removed
+    }
+
+    pub fn contains(&self, header_name: &HeaderName) -> bool {
+        self.0.iter().any(|(name, _)| name == header_name)
+    }
+
+    // The number of header lines, counting each value of a repeated header separately
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&HeaderName, &HeaderValue) -> bool) {
+        self.0.retain(|(name, value)| keep(name, value));
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(self.0.iter_mut())
+    }
+
+    // Adds a value of `header_name` after any existing ones
+    pub fn append(&mut self, header_name: HeaderName, header_value: HeaderValue) {
+        self.0.push((header_name, header_value));
     }
 
     pub fn set_accept(mut self, accept: impl Into<HeaderValue>) -> Self {
@@ -30,6 +122,37 @@ impl Headers {
         self
     }
 
+    pub fn set_accept_encoding(mut self, accept_encoding: impl Into<HeaderValue>) -> Self {
+        let accept_encoding = accept_encoding.into();
+        self.insert(HeaderName::AcceptEncoding, accept_encoding);
+        self
+    }
+
+    pub fn set_accept_ranges(mut self, accept_ranges: impl Into<HeaderValue>) -> Self {
+        let accept_ranges = accept_ranges.into();
+        self.insert(HeaderName::AcceptRanges, accept_ranges);
+        self
+    }
+
+    pub fn set_allow(mut self, allow: impl Into<HeaderValue>) -> Self {
+        let allow = allow.into();
+        self.insert(HeaderName::Allow, allow);
+        self
+    }
+
+    // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection
+    pub fn set_connection(mut self, connection: impl Into<HeaderValue>) -> Self {
+        let connection = connection.into();
+        self.insert(HeaderName::Connection, connection);
+        self
+    }
+
+    pub fn set_content_encoding(mut self, content_encoding: impl Into<HeaderValue>) -> Self {
+        let content_encoding = content_encoding.into();
+        self.insert(HeaderName::ContentEncoding, content_encoding);
+        self
+    }
+
     pub fn set_content_length(mut self, content_length: usize) -> Self {
         let content_length_string = content_length.to_string();
         let content_length = HeaderValue::new(content_length_string);
@@ -37,55 +160,123 @@ impl Headers {
         self
     }
 
+    pub fn set_content_range(mut self, content_range: impl Into<HeaderValue>) -> Self {
+        let content_range = content_range.into();
+        self.insert(HeaderName::ContentRange, content_range);
+        self
+    }
+
     pub fn set_content_type(mut self, content_type: impl Into<HeaderValue>) -> Self {
         let content_type = content_type.into();
         self.insert(HeaderName::ContentType, content_type);
         self
     }
 
+    pub fn set_etag(mut self, etag: impl Into<HeaderValue>) -> Self {
+        let etag = etag.into();
+        self.insert(HeaderName::ETag, etag);
+        self
+    }
+
     pub fn set_host(mut self, host: impl Into<HeaderValue>) -> Self {
         let host = host.into();
         self.insert(HeaderName::Host, host);
         self
     }
 
+    // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Keep-Alive
+    pub fn set_keep_alive(mut self, keep_alive: impl Into<HeaderValue>) -> Self {
+        let keep_alive = keep_alive.into();
+        self.insert(HeaderName::KeepAlive, keep_alive);
+        self
+    }
+
+    pub fn set_last_modified(mut self, last_modified: impl Into<HeaderValue>) -> Self {
+        let last_modified = last_modified.into();
+        self.insert(HeaderName::LastModified, last_modified);
+        self
+    }
+
+    pub fn set_location(mut self, location: impl Into<HeaderValue>) -> Self {
+        let location = location.into();
+        self.insert(HeaderName::Location, location);
+        self
+    }
+
+    pub fn set_transfer_encoding(mut self, transfer_encoding: impl Into<HeaderValue>) -> Self {
+        let transfer_encoding = transfer_encoding.into();
+        self.insert(HeaderName::TransferEncoding, transfer_encoding);
+        self
+    }
+
     pub fn set_user_agent(mut self, user_agent: impl Into<HeaderValue>) -> Self {
         let user_agent = user_agent.into();
         self.insert(HeaderName::UserAgent, user_agent);
         self
     }
 
-    pub fn user_agent(&self) -> Option<&HeaderValue> {
-        self.0.get(&HeaderName::UserAgent)
+    pub fn set_vary(mut self, vary: impl Into<HeaderValue>) -> Self {
+        let vary = vary.into();
+        self.insert(HeaderName::Vary, vary);
+        self
+    }
+
+    pub fn accept_encoding(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::AcceptEncoding)
+    }
+
+    pub fn connection(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::Connection)
+    }
+
+    pub fn content_encoding(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::ContentEncoding)
+    }
+
+    pub fn content_type(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::ContentType)
+    }
+
+    pub fn etag(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::ETag)
+    }
+
+    pub fn if_modified_since(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::IfModifiedSince)
+    }
+
+    pub fn if_range(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::IfRange)
     }
 
-    fn insert(&mut self, header_name: HeaderName, header_value: HeaderValue) {
-        self.0.insert(header_name, header_value);
+    pub fn if_unmodified_since(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::IfUnmodifiedSince)
+    }
+
+    pub fn last_modified(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::LastModified)
+    }
+
+    pub fn range(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::Range)
+    }
+
+    pub fn user_agent(&self) -> Option<&HeaderValue> {
+        self.get(&HeaderName::UserAgent)
     }
 }
 
 impl Default for Headers {
     fn default() -> Self {
-        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::default();
-        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection
-        // headers.insert(HeaderName::Connection, HeaderValue::new("keep-alive"));
-        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Keep-Alive
-        // headers.insert(
-        //     HeaderName::KeepAlive,
-        //     HeaderValue::new("timeout=5, max=1000"),
-        // );
-        Self::new(headers)
+        Self::new(Vec::default())
     }
 }
 
 impl fmt::Display for Headers {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0
-            .iter()
-            .fold(f, |formatter, (header_name, header_value)| {
-                let _ = write!(formatter, "{header_name}: {header_value}\r\n");
-                formatter
-            });
+        for (header_name, header_value) in self {
+            write!(f, "{header_name}: {header_value}\r\n")?;
+        }
         Ok(())
     }
 }
@@ -95,23 +286,102 @@ impl FromIterator<Header> for Headers {
         let mut headers = Self::default();
 
         for Header((header_name, header_value)) in iter {
-            headers.insert(header_name, header_value);
+            headers.append(header_name, header_value);
         }
 
         headers
     }
 }
 
-// `HeaderName` implements `Copy` but `HeaderValue` and `Headers` do not
-#[allow(clippy::copy_iterator)]
-impl<'a> Iterator for &'a Headers {
+impl FromIterator<(HeaderName, HeaderValue)> for Headers {
+    fn from_iter<T: IntoIterator<Item = (HeaderName, HeaderValue)>>(iter: T) -> Self {
+        let mut headers = Self::default();
+        headers.extend(iter);
+        headers
+    }
+}
+
+// Extending appends, so existing values are kept
+impl Extend<(HeaderName, HeaderValue)> for Headers {
+    fn extend<T: IntoIterator<Item = (HeaderName, HeaderValue)>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for Headers {
+    type Item = (HeaderName, HeaderValue);
+    type IntoIter = vec::IntoIter<(HeaderName, HeaderValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a HeaderName, &'a HeaderValue);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Headers {
+    type Item = (&'a HeaderName, &'a mut HeaderValue);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Iter<'a>(slice::Iter<'a, (HeaderName, HeaderValue)>);
+
+impl<'a> Iterator for Iter<'a> {
     type Item = (&'a HeaderName, &'a HeaderValue);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.iter().next()
+        self.0.next().map(|(name, value)| (name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(name, value)| (name, value))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+// Only values are mutable, so a header can't be renamed to one that is already present
+#[derive(Debug)]
+pub struct IterMut<'a>(slice::IterMut<'a, (HeaderName, HeaderValue)>);
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a HeaderName, &'a mut HeaderValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, value)| (&*name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
     }
 }
 
+impl DoubleEndedIterator for IterMut<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(name, value)| (&*name, value))
+    }
+}
+
+impl ExactSizeIterator for IterMut<'_> {}
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct Header(pub (HeaderName, HeaderValue));
 
@@ -124,7 +394,7 @@ impl Header {
         let (remainder, (header_name, _, _, header_value, _)) = (
             HeaderName::parse,
             parsing_utils::colon,
-            parsing_utils::space,
+            parsing_utils::optional_whitespace,
             HeaderValue::parse,
             parsing_utils::crlf,
         )
@@ -134,46 +404,155 @@ impl Header {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+// Header names are case-insensitive, so names we don't know are stored in lowercase
+// https://www.rfc-editor.org/rfc/rfc9110#section-5.1
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum HeaderName {
     Accept,
+    AcceptEncoding,
+    AcceptRanges,
+    Allow,
     Connection,
+    ContentEncoding,
     ContentLength,
+    ContentRange,
     ContentType,
+    ETag,
     Host,
+    IfMatch,
+    IfModifiedSince,
+    IfNoneMatch,
+    IfRange,
+    IfUnmodifiedSince,
     KeepAlive,
+    LastModified,
+    Location,
+    Range,
+    SetCookie,
+    TransferEncoding,
     UserAgent,
+    Vary,
+    // Only made by `new`, which lowercases it, so that names compare and hash case-insensitively
+    Other(CustomHeaderName),
 }
 
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct CustomHeaderName(String);
+
 impl HeaderName {
+    const KNOWN: &'static [Self] = &[
+        Self::Accept,
+        Self::AcceptEncoding,
+        Self::AcceptRanges,
+        Self::Allow,
+        Self::Connection,
+        Self::ContentEncoding,
+        Self::ContentLength,
+        Self::ContentRange,
+        Self::ContentType,
+        Self::ETag,
+        Self::Host,
+        Self::IfMatch,
+        Self::IfModifiedSince,
+        Self::IfNoneMatch,
+        Self::IfRange,
+        Self::IfUnmodifiedSince,
+        Self::KeepAlive,
+        Self::LastModified,
+        Self::Location,
+        Self::Range,
+        Self::SetCookie,
+        Self::TransferEncoding,
+        Self::UserAgent,
+        Self::Vary,
+    ];
+
+    pub fn new(header_name: &str) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|known| {
+                known
+                    .known_name()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(header_name))
+            })
+            .cloned()
+            .unwrap_or_else(|| Self::Other(CustomHeaderName(header_name.to_ascii_lowercase())))
+    }
+
     pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
-        branch::alt((
-            combinator::map(complete::tag(b"Accept"), |_| Self::Accept),
-            combinator::map(complete::tag(b"Connection"), |_| Self::Connection),
-            combinator::map(complete::tag(b"Content-Length"), |_| Self::ContentLength),
-            combinator::map(complete::tag(b"Content-Type"), |_| Self::ContentType),
-            combinator::map(complete::tag(b"Host"), |_| Self::Host),
-            combinator::map(complete::tag(b"Keep-Alive"), |_| Self::KeepAlive),
-            combinator::map(complete::tag(b"User-Agent"), |_| Self::UserAgent),
-        ))(bytes)
+        let (remainder, header_name_bytes) =
+            streaming::take_while1(parsing_utils::is_token_byte)(bytes)?;
+        // Token bytes are all ASCII so this is always valid UTF-8
+        let header_name_string = String::from_utf8_lossy(header_name_bytes);
+        let header_name = Self::new(&header_name_string);
+        Ok((remainder, header_name))
     }
-}
 
-impl fmt::Display for HeaderName {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let text = match self {
+    // The canonical spelling of names we know, which doesn't allocate
+    fn known_name(&self) -> Option<&'static str> {
+        let name = match self {
             Self::Accept => "Accept",
+            Self::AcceptEncoding => "Accept-Encoding",
+            Self::AcceptRanges => "Accept-Ranges",
+            Self::Allow => "Allow",
             Self::Connection => "Connection",
+            Self::ContentEncoding => "Content-Encoding",
             Self::ContentLength => "Content-Length",
+            Self::ContentRange => "Content-Range",
             Self::ContentType => "Content-Type",
+            Self::ETag => "ETag",
             Self::Host => "Host",
+            Self::IfMatch => "If-Match",
+            Self::IfModifiedSince => "If-Modified-Since",
+            Self::IfNoneMatch => "If-None-Match",
+            Self::IfRange => "If-Range",
+            Self::IfUnmodifiedSince => "If-Unmodified-Since",
             Self::KeepAlive => "Keep-Alive",
+            Self::LastModified => "Last-Modified",
+            Self::Location => "Location",
+            Self::Range => "Range",
+            Self::SetCookie => "Set-Cookie",
+            Self::TransferEncoding => "Transfer-Encoding",
             Self::UserAgent => "User-Agent",
+            Self::Vary => "Vary",
+            Self::Other(_) => return None,
         };
-        write!(f, "{text}")
+        Some(name)
     }
 }
 
+impl fmt::Display for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(CustomHeaderName(header_name)) => {
+                write!(f, "{}", canonicalise(header_name))
+            }
+            known => write!(f, "{}", known.known_name().unwrap_or_default()),
+        }
+    }
+}
+
+impl From<&str> for HeaderName {
+    fn from(header_name: &str) -> Self {
+        Self::new(header_name)
+    }
+}
+
+// Capitalises each hyphen-separated word, e.g. "x-request-id" becomes "X-Request-Id"
+fn canonicalise(header_name: &str) -> String {
+    header_name
+        .split('-')
+        .map(|word| {
+            let mut characters = word.chars();
+            match characters.next() {
+                None => String::new(),
+                Some(first) => first.to_ascii_uppercase().to_string() + characters.as_str(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct HeaderValue(String);
 
@@ -184,10 +563,20 @@ impl HeaderValue {
     }
 
     pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
-        let (remainder, header_value_bytes) = complete::take_until1("\r\n")(bytes)?;
-        let header_value_string =
-            str::from_utf8(header_value_bytes).expect("HeaderValue string is valid UTF8");
-        let header_value = Self::new(header_value_string);
+        // A stray CR, LF or NUL could be interpreted differently by other servers in the chain
+        // https://www.rfc-editor.org/rfc/rfc9110#section-5.5-5
+        let header_value_bytes = combinator::verify(
+            streaming::take_until("\r\n"),
+            |header_value_bytes: &[u8]| {
+                !header_value_bytes
+                    .iter()
+                    .any(|byte| matches!(byte, b'\0' | b'\r' | b'\n'))
+            },
+        );
+        let (remainder, header_value_string) =
+            combinator::map_res(header_value_bytes, str::from_utf8)(bytes)?;
+        // Whitespace before the value is consumed by `Header::parse`
+        let header_value = Self::new(header_value_string.trim_end_matches([' ', '\t']));
         Ok((remainder, header_value))
     }
 
@@ -195,6 +584,13 @@ impl HeaderValue {
         let int: Option<usize> = self.0.parse().ok();
         int
     }
+
+    // Whether a comma-separated list value, like that of `Connection`, contains `token`
+    pub fn contains_token(&self, token: &str) -> bool {
+        self.0
+            .split(',')
+            .any(|element| element.trim().eq_ignore_ascii_case(token))
+    }
 }
 
 impl fmt::Display for HeaderValue {
@@ -218,6 +614,12 @@ impl From<&str> for HeaderValue {
     }
 }
 
+impl From<String> for HeaderValue {
+    fn from(header_value: String) -> Self {
+        HeaderValue::new(header_value)
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 pub enum ContentType {
     #[default]
@@ -229,6 +631,8 @@ pub enum ContentType {
 mod tests {
     use core::str;
 
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
     use super::*;
 
     #[test]
@@ -285,7 +689,8 @@ mod tests {
             .set_user_agent("curl/7.64.1")
             .set_accept("*/*");
 
-        let serialized_headers = headers.to_string();
+        // The blank line terminating the header section is written by `Response`
+        let serialized_headers = format!("{headers}\r\n");
 
         let (remainder, deserialized_headers) =
             Headers::parse(serialized_headers.as_bytes()).expect("Serialized headers are valid");
@@ -300,7 +705,8 @@ mod tests {
             .set_content_type(ContentType::Text)
             .set_content_length(3);
 
-        let serialized_headers = headers.to_string();
+        // The blank line terminating the header section is written by `Response`
+        let serialized_headers = format!("{headers}\r\n");
 
         let (remainder, deserialized_headers) =
             Headers::parse(serialized_headers.as_bytes()).expect("Serialized headers are valid");
@@ -308,4 +714,242 @@ mod tests {
         assert!(remainder.is_empty());
         assert_eq!(headers, deserialized_headers);
     }
+
+    #[test]
+    fn connection_header_contains_token() {
+        let header_value = HeaderValue::new("Upgrade, Close");
+
+        assert!(header_value.contains_token("close"));
+        assert!(header_value.contains_token("upgrade"));
+        assert!(!header_value.contains_token("keep-alive"));
+    }
+
+    #[test]
+    fn deserialise_header_whose_name_extends_another() {
+        let bytes = b"Accept: */*\r\nAccept-Encoding: gzip\r\n\r\n";
+        let expected_headers = Headers::default()
+            .set_accept("*/*")
+            .set_accept_encoding("gzip");
+
+        let (remainder, headers) = Headers::parse(bytes).expect("Headers are valid");
+
+        assert!(remainder.is_empty());
+        assert_eq!(headers, expected_headers);
+    }
+
+    #[test]
+    fn deserialise_header_names_case_insensitively() {
+        let bytes = b"accept-encoding: gzip\r\nCONTENT-LENGTH: 0\r\n\r\n";
+        let expected_headers = Headers::default()
+            .set_accept_encoding("gzip")
+            .set_content_length(0);
+
+        let (remainder, headers) = Headers::parse(bytes).expect("Headers are valid");
+
+        assert!(remainder.is_empty());
+        assert_eq!(headers, expected_headers);
+    }
+
+    #[test]
+    fn deserialise_unknown_header() {
+        let bytes = b"x-Request-ID:abc123 \r\n";
+        let expected_header = Header::new(
+            HeaderName::Other(CustomHeaderName(String::from("x-request-id"))),
+            HeaderValue::new("abc123"),
+        );
+
+        let (remainder, header) = Header::parse(bytes).expect("Header is valid");
+
+        assert!(remainder.is_empty());
+        assert_eq!(header, expected_header);
+        assert_eq!(header.0 .0, HeaderName::from("X-REQUEST-ID"));
+    }
+
+    #[test]
+    fn unknown_header_names_are_case_insensitive() {
+        let names = [
+            HeaderName::new("X-Request-Id"),
+            HeaderName::new("x-REQUEST-id"),
+        ];
+        let hash = |header_name: &HeaderName| {
+            let mut hasher = DefaultHasher::new();
+            header_name.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(names[0], names[1]);
+        assert_eq!(hash(&names[0]), hash(&names[1]));
+        assert_eq!(names[0].to_string(), "X-Request-Id");
+    }
+
+    #[test]
+    fn deserialise_header_with_empty_value() {
+        let bytes = b"Cookie: \r\n";
+        let expected_header = Header::new(HeaderName::from("cookie"), HeaderValue::new(""));
+
+        let (remainder, header) = Header::parse(bytes).expect("Header is valid");
+
+        assert!(remainder.is_empty());
+        assert_eq!(header, expected_header);
+    }
+
+    #[test]
+    fn header_names_are_serialised_in_canonical_case() {
+        assert_eq!(HeaderName::from("content-type").to_string(), "Content-Type");
+        assert_eq!(HeaderName::from("x-request-id").to_string(), "X-Request-Id");
+        assert_eq!(
+            HeaderName::from("WWW-Authenticate").to_string(),
+            "Www-Authenticate"
+        );
+    }
+
+    #[test]
+    fn header_name_must_be_a_token() {
+        let bytes = b"Bad Name: value\r\n";
+
+        let result = Header::parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn repeated_headers_are_all_kept_in_order() {
+        let bytes = b"Set-Cookie: a=1\r\nHost: localhost\r\nSet-Cookie: b=2\r\n\r\n";
+
+        let (_, headers) = Headers::parse(bytes).expect("Headers are valid");
+
+        let cookies: Vec<_> = headers.get_all(&HeaderName::SetCookie).collect();
+        assert_eq!(
+            cookies,
+            vec![&HeaderValue::new("a=1"), &HeaderValue::new("b=2")]
+        );
+        assert_eq!(format!("{headers}\r\n").as_bytes(), bytes);
+    }
+
+    #[test]
+    fn combine_list_header_values() {
+        let mut headers = Headers::default();
+        headers.append(HeaderName::Accept, HeaderValue::new("text/html"));
+        headers.append(HeaderName::Accept, HeaderValue::new("*/*;q=0.8"));
+        headers.append(HeaderName::SetCookie, HeaderValue::new("a=1"));
+        headers.append(HeaderName::SetCookie, HeaderValue::new("b=2"));
+
+        assert_eq!(
+            headers.get_combined(&HeaderName::Accept),
+            Some(HeaderValue::new("text/html, */*;q=0.8"))
+        );
+        assert_eq!(
+            headers.get_combined(&HeaderName::SetCookie),
+            Some(HeaderValue::new("a=1"))
+        );
+        assert_eq!(headers.get_combined(&HeaderName::Host), None);
+    }
+
+    #[test]
+    fn insert_replaces_all_values_in_place() {
+        let mut headers = Headers::default();
+        headers.append(HeaderName::Vary, HeaderValue::new("Accept"));
+        headers.append(HeaderName::Host, HeaderValue::new("localhost"));
+        headers.append(HeaderName::Vary, HeaderValue::new("Cookie"));
+
+        headers.insert(HeaderName::Vary, HeaderValue::new("Accept-Encoding"));
+
+        assert_eq!(
+            headers.to_string(),
+            "Vary: Accept-Encoding\r\nHost: localhost\r\n"
+        );
+    }
+
+    #[test]
+    fn serialise_headers_in_insertion_order() {
+        let headers = Headers::default()
+            .set_user_agent("curl/7.64.1")
+            .set_host("localhost:4221")
+            .set_accept("*/*")
+            .set_content_type(ContentType::Text);
+
+        assert_eq!(
+            headers.to_string(),
+            "User-Agent: curl/7.64.1\r\nHost: localhost:4221\r\nAccept: */*\r\nContent-Type: text/plain\r\n"
+        );
+    }
+
+    fn make_headers() -> Headers {
+        [
+            (HeaderName::Host, HeaderValue::new("localhost")),
+            (HeaderName::SetCookie, HeaderValue::new("a=1")),
+            (HeaderName::Accept, HeaderValue::new("*/*")),
+            (HeaderName::SetCookie, HeaderValue::new("b=2")),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn iterate_over_every_header_once() {
+        let headers = make_headers();
+
+        let names: Vec<String> = headers.iter().map(|(name, _)| name.to_string()).collect();
+
+        assert_eq!(names, ["Host", "Set-Cookie", "Accept", "Set-Cookie"]);
+        assert_eq!(headers.iter().len(), headers.len());
+        assert_eq!((&headers).into_iter().count(), 4);
+        assert_eq!(headers.into_iter().count(), 4);
+    }
+
+    #[test]
+    fn rewrite_header_values_in_place() {
+        let mut headers = make_headers();
+
+        for (name, value) in &mut headers {
+            if *name == HeaderName::SetCookie {
+                *value = HeaderValue::new(format!("{value}; Secure"));
+            }
+        }
+
+        let cookies: Vec<String> = headers
+            .get_all(&HeaderName::SetCookie)
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(cookies, ["a=1; Secure", "b=2; Secure"]);
+    }
+
+    #[test]
+    fn remove_every_value_of_a_header() {
+        let mut headers = make_headers();
+
+        let removed = headers.remove(&HeaderName::SetCookie);
+
+        assert_eq!(removed, Some(HeaderValue::new("a=1")));
+        assert!(!headers.contains(&HeaderName::SetCookie));
+        assert!(headers.contains(&HeaderName::Host));
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.remove(&HeaderName::SetCookie), None);
+    }
+
+    #[test]
+    fn retain_and_extend_headers() {
+        let mut headers = make_headers();
+
+        headers.retain(|name, _| *name != HeaderName::Accept);
+        headers.extend([(HeaderName::Host, HeaderValue::new("example.com"))]);
+
+        assert_eq!(
+            headers.to_string(),
+            "Host: localhost\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\nHost: example.com\r\n"
+        );
+    }
+
+    #[test]
+    fn header_value_must_be_valid_utf8_without_control_characters() {
+        for bytes in [
+            &b"User-Agent: \xff\xfe\r\n"[..],
+            b"User-Agent: a\nb\r\n",
+            b"User-Agent: a\0b\r\n",
+        ] {
+            let result = Header::parse(bytes);
+
+            assert!(matches!(result, Err(nom::Err::Error(_))));
+        }
+    }
 }
@@ -0,0 +1,164 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// Formats `time` as an IMF-fixdate, like "Sun, 06 Nov 1994 08:49:37 GMT". Times before 1970 are
+// formatted as the epoch.
+// https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7
+pub fn format(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = seconds / SECONDS_PER_DAY;
+    let (year, month, day) = civil_from_days(days);
+    let seconds_of_day = seconds % SECONDS_PER_DAY;
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[(days % 7) as usize],
+        MONTH_NAMES[month as usize - 1],
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+    )
+}
+
+// Parses any of the three HTTP-date formats, returning `None` for anything else
+pub fn parse(date: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = date.split_ascii_whitespace().collect();
+    let (day, month, year, time) = match parts.as_slice() {
+        // IMF-fixdate: "Sun, 06 Nov 1994 08:49:37 GMT"
+        [_, day, month, year, time, "GMT"] => (*day, *month, parse_digits(year, 4..=4)?, *time),
+        // RFC 850: "Sunday, 06-Nov-94 08:49:37 GMT"
+        [_, date, time, "GMT"] => {
+            let mut date = date.split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+            // Dates are never before 1970, so two digit years before 70 are in the 2000s
+            let year = match parse_digits(year, 2..=2)? {
+                year if year < 70 => 2000 + year,
+                year => 1900 + year,
+            };
+            (day, month, year, *time)
+        }
+        // asctime: "Sun Nov  6 08:49:37 1994"
+        [_, month, day, time, year] => (*day, *month, parse_digits(year, 4..=4)?, *time),
+        _ => return None,
+    };
+
+    let day = parse_digits(day, 1..=2)?;
+    let month = MONTH_NAMES.iter().position(|name| *name == month)? as u64 + 1;
+    let mut time = time.split(':');
+    let hours = parse_digits(time.next()?, 2..=2)?;
+    let minutes = parse_digits(time.next()?, 2..=2)?;
+    let seconds = parse_digits(time.next()?, 2..=2)?;
+    if time.next().is_some()
+        || !(1970..=9999).contains(&year)
+        || !(1..=31).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn parse_digits(digits: &str, length: std::ops::RangeInclusive<usize>) -> Option<u64> {
+    let is_valid =
+        length.contains(&digits.len()) && digits.bytes().all(|byte| byte.is_ascii_digit());
+    is_valid.then(|| digits.parse().ok()).flatten()
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian calendar
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// The inverse of `civil_from_days`, for dates from 1970 onwards
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from RFC 9110
+    const TIME: u64 = 784_111_777;
+
+    #[test]
+    fn format_imf_fixdate() {
+        let time = UNIX_EPOCH + Duration::from_secs(TIME);
+
+        assert_eq!(format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn parse_all_formats() {
+        let time = Some(UNIX_EPOCH + Duration::from_secs(TIME));
+
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), time);
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), time);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), time);
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        for date in [
+            "",
+            "yesterday",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1969 08:49:37 GMT",
+            "Sun, +6 Nov 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(parse(date), None, "Parsing {date}");
+        }
+    }
+
+    #[test]
+    fn format_then_parse_round_trips() {
+        for seconds in [0, TIME, 1_700_000_000, 4_102_444_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+
+            assert_eq!(parse(&format(time)), Some(time));
+        }
+    }
+}
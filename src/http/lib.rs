@@ -1,15 +1,47 @@
 mod body;
+mod chunked;
+mod compression;
+mod conditional;
+mod connection;
 mod error;
+mod extract;
+mod file_root;
+mod handler;
 mod headers;
+mod http_date;
+mod limits;
 mod method;
+mod middleware;
+mod mime;
 pub mod parsing_utils;
 mod path;
+mod range;
 mod request;
+mod request_target;
 mod response;
 mod response_builder;
+mod router;
+mod static_files;
 mod status_line;
 mod version;
 
+pub use body::{Body, BodySender, BodyStream};
+pub use conditional::EntityTag;
+pub use connection::{Connection, ConnectionError, KeepAlive};
+pub use error::Error;
+pub use extract::{Form, FromRequest, Query, Rejection, SharedState, State};
+pub use file_root::{FileRoot, FileRootError};
+pub use handler::{BoxFuture, Handler, IntoResponse};
+pub use headers::{CustomHeaderName, HeaderName, HeaderValue, Headers};
+pub use limits::ParserLimits;
 pub use method::Method;
+pub use middleware::{CatchPanic, Logger, Middleware, Next, RequestId, RewriteHeaders, Timing};
+pub use path::Path;
 pub use request::Request;
+pub use request_target::RequestTarget;
 pub use response::Response;
+pub use response_builder::ResponseBuilder;
+pub use router::{Params, Router};
+pub use static_files::StaticFiles;
+pub use status_line::{CustomStatus, Status};
+pub use version::Version;
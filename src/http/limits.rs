@@ -0,0 +1,31 @@
+// Bounds on the size of a request, so a client can't make us buffer or allocate arbitrarily.
+// Requests exceeding them are rejected as soon as the limit is passed, without waiting for the
+// rest of the request.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ParserLimits {
+    // The longest request line accepted, excluding its CRLF; longer ones get a 414 response
+    pub max_request_line_length: usize,
+    // The most bytes of header fields accepted, including each field's CRLF and the blank line
+    // ending them; more get a 431 response
+    pub max_header_bytes: usize,
+    // The most header fields accepted; more get a 431 response
+    pub max_header_count: usize,
+    // The largest body accepted; larger ones get a 413 response. Chunked bodies are measured
+    // once decoded.
+    pub max_body_size: usize,
+    // The most bytes of chunk-size lines, chunk extensions and trailers accepted in a chunked
+    // body, separately from its data; more get a 413 response
+    pub max_chunked_framing_size: usize,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_request_line_length: 8 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_header_count: 100,
+            max_body_size: 8 * 1024 * 1024,
+            max_chunked_framing_size: 1024 * 1024,
+        }
+    }
+}
@@ -17,16 +17,27 @@ pub enum Method {
 }
 
 impl Method {
+    pub const ALL: &'static [Self] = &[
+        Self::Get,
+        Self::Head,
+        Self::Post,
+        Self::Put,
+        Self::Delete,
+        Self::Connect,
+        Self::Options,
+        Self::Trace,
+    ];
+
     pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
         branch::alt((
-            combinator::map(bytes::complete::tag(b"GET"), |_| Self::Get),
-            combinator::map(bytes::complete::tag(b"HEAD"), |_| Self::Head),
-            combinator::map(bytes::complete::tag(b"POST"), |_| Self::Post),
-            combinator::map(bytes::complete::tag(b"PUT"), |_| Self::Put),
-            combinator::map(bytes::complete::tag(b"DELETE"), |_| Self::Delete),
-            combinator::map(bytes::complete::tag(b"CONNECT"), |_| Self::Connect),
-            combinator::map(bytes::complete::tag(b"OPTIONS"), |_| Self::Options),
-            combinator::map(bytes::complete::tag(b"TRACE"), |_| Self::Trace),
+            combinator::map(bytes::streaming::tag(b"GET"), |_| Self::Get),
+            combinator::map(bytes::streaming::tag(b"HEAD"), |_| Self::Head),
+            combinator::map(bytes::streaming::tag(b"POST"), |_| Self::Post),
+            combinator::map(bytes::streaming::tag(b"PUT"), |_| Self::Put),
+            combinator::map(bytes::streaming::tag(b"DELETE"), |_| Self::Delete),
+            combinator::map(bytes::streaming::tag(b"CONNECT"), |_| Self::Connect),
+            combinator::map(bytes::streaming::tag(b"OPTIONS"), |_| Self::Options),
+            combinator::map(bytes::streaming::tag(b"TRACE"), |_| Self::Trace),
         ))(bytes)
     }
 }
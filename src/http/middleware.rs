@@ -0,0 +1,486 @@
+use std::{
+    any::Any,
+    future::{self, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    handler::BoxFuture,
+    headers::{HeaderName, HeaderValue, Headers},
+    request::Request,
+    response::Response,
+    response_builder::ResponseBuilder,
+    router::Router,
+};
+
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+// Wraps the handling of requests with behaviour common to every route, added to a `Router` with
+// `Router::layer`. Middleware can change the request before calling `next`, change the response
+// it returns, or respond without calling it at all.
+pub trait Middleware: Send + Sync + 'static {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder>;
+}
+
+// The layers after the current one, followed by the router's routes
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Box<dyn Middleware>],
+    router: &'a Router,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Box<dyn Middleware>], router: &'a Router) -> Self {
+        Self { layers, router }
+    }
+
+    pub fn run(self, request: Request) -> BoxFuture<'a, ResponseBuilder> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.call(request, Next::new(layers, self.router)),
+            None => self.router.call_route(request),
+        }
+    }
+}
+
+// Prints each request line and the status it was responded to with
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Logger;
+
+impl Middleware for Logger {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+        let request_line = format!(
+            "{} {} {}",
+            request.method(),
+            request.raw_target(),
+            request.version()
+        );
+        let request_id = request
+            .headers()
+            .get(&request_id_header_name())
+            .map(|request_id| format!(" ({request_id})"))
+            .unwrap_or_default();
+        Box::pin(async move {
+            println!("Received request{request_id}: {request_line}");
+            let response = next.run(request).await;
+            println!(
+                "Responding{request_id} to {request_line} with: {}",
+                response.status()
+            );
+            response
+        })
+    }
+}
+
+// Reports how long the layers after it took to respond in a `Server-Timing` header
+// https://www.w3.org/TR/server-timing/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timing;
+
+impl Middleware for Timing {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let mut response = next.run(request).await;
+            let milliseconds = start.elapsed().as_secs_f64() * 1000.0;
+            response.headers_mut().append(
+                HeaderName::new("Server-Timing"),
+                HeaderValue::new(format!("app;dur={milliseconds:.3}")),
+            );
+            response
+        })
+    }
+}
+
+// Responds 500 when the layers after it panic, rather than dropping the connection. The panic is
+// still reported by the panic hook.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CatchPanic;
+
+impl Middleware for CatchPanic {
+    fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+        // Handlers can panic while extracting their arguments, before their future is polled
+        let future = match panic::catch_unwind(AssertUnwindSafe(|| next.run(request))) {
+            Ok(future) => future,
+            Err(payload) => return Box::pin(future::ready(panic_response(payload))),
+        };
+        Box::pin(async move { CatchUnwind(future).await.unwrap_or_else(panic_response) })
+    }
+}
+
+fn panic_response(payload: Box<dyn Any + Send>) -> ResponseBuilder {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    eprintln!("Handling a request panicked with: {message}");
+    Response::internal_server_error()
+}
+
+struct CatchUnwind<'a>(BoxFuture<'a, ResponseBuilder>);
+
+impl Future for CatchUnwind<'_> {
+    type Output = thread::Result<ResponseBuilder>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = &mut self.0;
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(response)) => Poll::Ready(Ok(response)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+// Gives each request an `X-Request-Id` header, keeping any the client sent, so handlers and later
+// layers can refer to it. The response has the same header.
+#[derive(Debug)]
+pub struct RequestId {
+    // Distinguishes the IDs of different runs of the server
+    prefix: String,
+    count: AtomicU64,
+}
+
+impl RequestId {
+    pub fn new() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            prefix: format!("{started:x}"),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn generate(&self) -> HeaderValue {
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        HeaderValue::new(format!("{}-{count:x}", self.prefix))
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for RequestId {
+    fn call<'a>(&'a self, mut request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+        let header_name = request_id_header_name();
+        let request_id = request
+            .headers()
+            .get(&header_name)
+            .filter(|request_id| is_valid_request_id(&request_id.to_string()))
+            .cloned()
+            .unwrap_or_else(|| self.generate());
+        request
+            .headers_mut()
+            .insert(header_name.clone(), request_id.clone());
+        Box::pin(async move {
+            let mut response = next.run(request).await;
+            response.headers_mut().insert(header_name, request_id);
+            response
+        })
+    }
+}
+
+fn request_id_header_name() -> HeaderName {
+    HeaderName::new("X-Request-Id")
+}
+
+fn is_valid_request_id(request_id: &str) -> bool {
+    (1..=MAX_REQUEST_ID_LENGTH).contains(&request_id.len())
+        && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+// Sets or removes headers of requests before they're routed, and of the responses to them
+#[derive(Clone, Debug, Default)]
+pub struct RewriteHeaders {
+    request: Vec<Rewrite>,
+    response: Vec<Rewrite>,
+}
+
+impl RewriteHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces every value of `name`
+    pub fn set_request_header(mut self, name: HeaderName, value: impl Into<HeaderValue>) -> Self {
+        self.request.push(Rewrite::Set(name, value.into()));
+        self
+    }
+
+    pub fn remove_request_header(mut self, name: HeaderName) -> Self {
+        self.request.push(Rewrite::Remove(name));
+        self
+    }
+
+    // Replaces every value of `name`
+    pub fn set_response_header(mut self, name: HeaderName, value: impl Into<HeaderValue>) -> Self {
+        self.response.push(Rewrite::Set(name, value.into()));
+        self
+    }
+
+    // Adds a value of `name` after any the response already has
+    pub fn append_response_header(
+        mut self,
+        name: HeaderName,
+        value: impl Into<HeaderValue>,
+    ) -> Self {
+        self.response.push(Rewrite::Append(name, value.into()));
+        self
+    }
+
+    pub fn remove_response_header(mut self, name: HeaderName) -> Self {
+        self.response.push(Rewrite::Remove(name));
+        self
+    }
+}
+
+impl Middleware for RewriteHeaders {
+    fn call<'a>(&'a self, mut request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+        for rewrite in &self.request {
+            rewrite.apply(request.headers_mut());
+        }
+        Box::pin(async move {
+            let mut response = next.run(request).await;
+            for rewrite in &self.response {
+                rewrite.apply(response.headers_mut());
+            }
+            response
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Rewrite {
+    Set(HeaderName, HeaderValue),
+    Append(HeaderName, HeaderValue),
+    Remove(HeaderName),
+}
+
+impl Rewrite {
+    fn apply(&self, headers: &mut Headers) {
+        match self {
+            Self::Set(name, value) => headers.insert(name.clone(), value.clone()),
+            Self::Append(name, value) => headers.append(name.clone(), value.clone()),
+            Self::Remove(name) => {
+                headers.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        method::Method, path::Path, request_target::RequestTarget, status_line::Status,
+        version::Version,
+    };
+
+    use super::*;
+
+    fn request(path: &str, headers: Headers) -> Request {
+        let target = RequestTarget::Origin(Path::new(path));
+        Request::new(Method::Get, target, Version::default(), headers, None)
+    }
+
+    // Records when it sees the request and the response
+    struct Record {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Record {
+        fn call<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, ResponseBuilder> {
+            Box::pin(async move {
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} request", self.name));
+                let response = next.run(request).await;
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} response", self.name));
+                response
+            })
+        }
+    }
+
+    // Responds 403 without calling the layers after it
+    struct Forbid;
+
+    impl Middleware for Forbid {
+        fn call<'a>(
+            &'a self,
+            _request: Request,
+            _next: Next<'a>,
+        ) -> BoxFuture<'a, ResponseBuilder> {
+            Box::pin(future::ready(Response::forbidden()))
+        }
+    }
+
+    fn header<'a>(response: &'a ResponseBuilder, name: &str) -> Option<&'a HeaderValue> {
+        response.headers().get(&HeaderName::new(name))
+    }
+
+    #[tokio::test]
+    async fn layers_run_in_the_order_they_are_added() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let record = |name| Record {
+            name,
+            log: Arc::clone(&log),
+        };
+        let handler_log = Arc::clone(&log);
+        let router = Router::new()
+            .get("/", move || {
+                handler_log.lock().unwrap().push("handler".to_string());
+                async { "ok" }
+            })
+            .layer(record("outer"))
+            .layer(record("inner"));
+
+        router.handle(request("/", Headers::default())).await;
+        router.handle(request("/missing", Headers::default())).await;
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "outer request",
+                "inner request",
+                "handler",
+                "inner response",
+                "outer response",
+                // Requests the router responds to itself go through the layers too
+                "outer request",
+                "inner request",
+                "inner response",
+                "outer response",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn layers_can_respond_without_calling_next() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let router = Router::new()
+            .get("/", || async { "ok" })
+            .layer(Record {
+                name: "outer",
+                log: Arc::clone(&log),
+            })
+            .layer(Forbid)
+            .layer(Record {
+                name: "inner",
+                log: Arc::clone(&log),
+            });
+
+        let response = router.handle(request("/", Headers::default())).await;
+
+        assert_eq!(response.status(), &Status::Forbidden);
+        assert_eq!(*log.lock().unwrap(), ["outer request", "outer response"]);
+    }
+
+    #[tokio::test]
+    async fn panics_are_caught() {
+        async fn panic_when_polled() -> &'static str {
+            panic!("Handler panicked")
+        }
+
+        let router = Router::new()
+            .get("/sync", || -> future::Ready<&'static str> {
+                panic!("Handler panicked")
+            })
+            .get("/async", panic_when_polled)
+            .layer(CatchPanic);
+
+        for path in ["/sync", "/async"] {
+            let response = router.handle(request(path, Headers::default())).await;
+
+            assert_eq!(response.status(), &Status::InternalServerError, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_given_ids() {
+        let router = Router::new()
+            .get("/", |headers: Headers| async move {
+                headers
+                    .get(&HeaderName::new("X-Request-Id"))
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .layer(RequestId::new());
+
+        let first = router.handle(request("/", Headers::default())).await;
+        let second = router.handle(request("/", Headers::default())).await;
+        let first_id = header(&first, "X-Request-Id").unwrap().to_string();
+        let second_id = header(&second, "X-Request-Id").unwrap().to_string();
+
+        assert_ne!(first_id, second_id);
+        assert_eq!(
+            first.build().body().unwrap().as_bytes(),
+            first_id.as_bytes()
+        );
+
+        let headers = Headers::new(vec![(
+            HeaderName::new("X-Request-Id"),
+            HeaderValue::new("client-id"),
+        )]);
+        let response = router.handle(request("/", headers)).await;
+
+        assert_eq!(
+            header(&response, "X-Request-Id"),
+            Some(&HeaderValue::new("client-id"))
+        );
+    }
+
+    #[tokio::test]
+    async fn responses_are_timed() {
+        let router = Router::new().layer(Timing);
+
+        let response = router.handle(request("/", Headers::default())).await;
+
+        let server_timing = header(&response, "Server-Timing").unwrap().to_string();
+        assert!(server_timing.starts_with("app;dur="), "{server_timing}");
+    }
+
+    #[tokio::test]
+    async fn headers_are_rewritten() {
+        let router = Router::new()
+            .get("/", |headers: Headers| async move {
+                let user_agent = headers.user_agent().map(ToString::to_string);
+                Response::ok()
+                    .set_body(user_agent.unwrap_or_default())
+                    .set_location("/elsewhere")
+            })
+            .layer(
+                RewriteHeaders::new()
+                    .set_request_header(HeaderName::UserAgent, "rewritten")
+                    .remove_response_header(HeaderName::Location)
+                    .set_response_header(HeaderName::new("X-Frame-Options"), "DENY")
+                    .append_response_header(HeaderName::Vary, "Origin"),
+            );
+        let headers = Headers::default().set_user_agent("original");
+
+        let response = router.handle(request("/", headers)).await;
+
+        assert_eq!(header(&response, "Location"), None);
+        assert_eq!(
+            header(&response, "X-Frame-Options"),
+            Some(&HeaderValue::new("DENY"))
+        );
+        assert_eq!(header(&response, "Vary"), Some(&HeaderValue::new("Origin")));
+        assert_eq!(response.build().body().unwrap().as_bytes(), b"rewritten");
+    }
+}
@@ -0,0 +1,139 @@
+use std::{path::Path, str};
+
+pub const DEFAULT_MEDIA_TYPE: &str = "application/octet-stream";
+
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types
+const MEDIA_TYPES_BY_EXTENSION: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("md", "text/markdown"),
+    ("mjs", "text/javascript"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("ttf", "font/ttf"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("xml", "application/xml"),
+    ("zip", "application/zip"),
+];
+
+// Formats recognisable from their first bytes
+// https://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\0asm", "application/wasm"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b\x08", "application/gzip"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+// Looks up the media type of a file by its extension, ignoring case
+pub fn from_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    MEDIA_TYPES_BY_EXTENSION
+        .iter()
+        .find(|(known_extension, _)| known_extension.eq_ignore_ascii_case(extension))
+        .map(|(_, media_type)| *media_type)
+}
+
+// Guesses the media type of `bytes` from their content, for files without a known extension
+// https://mimesniff.spec.whatwg.org/
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    if let Some((_, media_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+    {
+        return Some(media_type);
+    }
+
+    let text = str::from_utf8(bytes).ok()?;
+    let is_text = !text
+        .chars()
+        .any(|character| character.is_control() && !matches!(character, '\t' | '\n' | '\r'));
+    if !is_text {
+        return None;
+    }
+    let text = text.trim_start();
+    let starts_with = |prefix: &str| {
+        text.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    };
+    let is_html = starts_with("<!doctype html") || starts_with("<html>") || starts_with("<html ");
+    match is_html {
+        true => Some("text/html"),
+        false => Some("text/plain"),
+    }
+}
+
+// Text types default to ASCII unless a charset is given, but the files we serve are usually UTF-8
+// https://www.rfc-editor.org/rfc/rfc2046#section-4.1.2
+pub fn with_charset(media_type: &str) -> String {
+    if media_type.starts_with("text/") {
+        format!("{media_type}; charset=utf-8")
+    } else {
+        media_type.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_type_from_extension() {
+        assert_eq!(from_path(Path::new("index.html")), Some("text/html"));
+        assert_eq!(from_path(Path::new("app.JS")), Some("text/javascript"));
+        assert_eq!(
+            from_path(Path::new("module.wasm")),
+            Some("application/wasm")
+        );
+        assert_eq!(
+            from_path(Path::new("archive.tar.gz")),
+            Some("application/gzip")
+        );
+        assert_eq!(from_path(Path::new("README")), None);
+        assert_eq!(from_path(Path::new("file.unknown")), None);
+    }
+
+    #[test]
+    fn sniff_media_type() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff(b"  <!DOCTYPE html><html></html>"), Some("text/html"));
+        assert_eq!(sniff(b"<html lang=\"en\">"), Some("text/html"));
+        assert_eq!(sniff(b"<HTML>"), Some("text/html"));
+        assert_eq!(sniff("Hello, wörld!\n".as_bytes()), Some("text/plain"));
+        assert_eq!(sniff(b"\0\x01\x02\x03"), None);
+        assert_eq!(sniff(b"\xff\xfe\xfd"), None);
+    }
+
+    #[test]
+    fn text_types_have_a_charset() {
+        assert_eq!(with_charset("text/html"), "text/html; charset=utf-8");
+        assert_eq!(with_charset("image/png"), "image/png");
+    }
+}
@@ -1,13 +1,22 @@
-use nom::{bytes::complete, character, IResult};
+use nom::{bytes::streaming, character, IResult};
 
 pub fn colon(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
-    complete::take_while1(|byte| byte == b':')(bytes)
+    streaming::take_while1(|byte| byte == b':')(bytes)
 }
 
 pub fn crlf(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
-    complete::tag(b"\r\n")(bytes)
+    streaming::tag(b"\r\n")(bytes)
 }
 
 pub fn space(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
-    complete::take_while1(character::is_space)(bytes)
+    streaming::take_while1(character::is_space)(bytes)
+}
+
+pub fn optional_whitespace(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
+    streaming::take_while(character::is_space)(bytes)
+}
+
+// https://www.rfc-editor.org/rfc/rfc9110#section-5.6.2
+pub fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
 }
@@ -1,37 +1,290 @@
-use nom::{bytes, IResult};
 use std::str;
 
+// A request target split into its path and query components. The path is split into segments
+// which are each percent-decoded, and normalised, so equivalent targets like "/a/./b" and
+// "/a//c/../b" have the same path, while the target as it was received is kept as the raw target.
+// Slashes and percent signs decoded within a segment stay encoded in the path, so "/a%2Fb" has
+// the one segment "a/b" and the path "/a%2Fb".
+// https://www.rfc-editor.org/rfc/rfc3986#section-3
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
-pub struct Path(String);
+pub struct Path {
+    raw: String,
+    path: String,
+    query: Option<String>,
+}
 
 impl Path {
-    pub fn new(path: impl Into<String>) -> Self {
-        let path = path.into();
-        Self(path)
-    }
-
-    pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
-        let (remainder, path_bytes) = bytes::complete::take_while1(|byte| byte != b' ')(bytes)?;
-        let path_string = str::from_utf8(path_bytes).expect("Path string is valid UTF8");
-        let path = Self::new(path_string);
-        Ok((remainder, path))
+    pub fn new(target: impl Into<String>) -> Self {
+        let raw = target.into();
+        let (path, query) = match raw.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (raw.as_str(), None),
+        };
+        let segments: Vec<String> = normalise(path)
+            .iter()
+            .map(|segment| encode_segment(segment))
+            .collect();
+        let path = format!("/{}", segments.join("/"));
+        Self { raw, path, query }
     }
 
     pub fn starts_with(&self, pattern: &str) -> bool {
-        self.0.starts_with(pattern)
+        self.path.starts_with(pattern)
     }
 
+    // Strips `prefix` from the path component, keeping the query and raw target
     pub fn strip_prefix(&self, prefix: &str) -> Option<Self> {
-        self.0.strip_prefix(prefix).map(Self::new)
+        let path = self.path.strip_prefix(prefix)?;
+        let path = Self {
+            raw: self.raw.clone(),
+            path: path.to_string(),
+            query: self.query.clone(),
+        };
+        Some(path)
     }
 
+    // The normalised path component, decoded but for slashes and percent signs within segments,
+    // without the query
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.path
+    }
+
+    // The decoded segments after the path's leading slash. A path ending in a slash has an empty
+    // last segment, as does "/".
+    pub(crate) fn segments(&self) -> Vec<String> {
+        let path = self.path.strip_prefix('/').unwrap_or(&self.path);
+        path.split('/')
+            .map(|segment| String::from_utf8_lossy(&percent_decode(segment)).into_owned())
+            .collect()
+    }
+
+    // The target as it was received
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    // The raw query component, without the leading '?'
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    // Decoded name - value pairs from the query, in order, including repeated names
+    pub fn query_pairs(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.query.iter().flat_map(|query| parse_urlencoded(query))
+    }
+
+    // Returns the first value of the query parameter `name`
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query_params(name).next()
+    }
+
+    pub fn query_params<'a>(&'a self, name: &'a str) -> impl Iterator<Item = String> + 'a {
+        self.query_pairs()
+            .filter(move |(pair_name, _)| pair_name == name)
+            .map(|(_, value)| value)
     }
 }
 
 impl PartialEq<str> for Path {
     fn eq(&self, other: &str) -> bool {
-        self.0 == other
+        self.path == other
+    }
+}
+
+// Parses a query, or a form body of type "application/x-www-form-urlencoded"
+// https://url.spec.whatwg.org/#urlencoded-parsing
+pub(crate) fn parse_urlencoded(string: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query_component(name), decode_query_component(value))
+        })
+}
+
+// Rejects malformed escapes, and encoded NULs which could truncate file names, anywhere in the
+// target, as well as paths that don't decode to UTF-8
+pub(crate) fn is_valid_target(target: &str) -> bool {
+    let bytes = target.as_bytes();
+    let are_escapes_valid = bytes.iter().enumerate().all(|(index, byte)| {
+        *byte != b'%'
+            || bytes.get(index + 1..index + 3).is_some_and(|hex_digits| {
+                hex_digits.iter().all(u8::is_ascii_hexdigit) && hex_digits != b"00"
+            })
+    });
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    are_escapes_valid && str::from_utf8(&percent_decode(path)).is_ok()
+}
+
+// Splits the path into segments before decoding them, then removes "." and ".." segments, and
+// empty ones from repeated slashes, so the path can't climb above the root. A path ending in a
+// slash, or "." or "..", has an empty last segment, as does the root.
+// https://www.rfc-editor.org/rfc/rfc3986#section-5.2.4
+fn normalise(path: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut is_directory = true;
+    for segment in path.split('/') {
+        let segment = String::from_utf8_lossy(&percent_decode(segment)).into_owned();
+        is_directory = matches!(segment.as_str(), "" | "." | "..");
+        match segment.as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    if is_directory || segments.is_empty() {
+        segments.push(String::new());
+    }
+    segments
+}
+
+// Keeps the separators and escapes in a decoded segment from being mistaken for those of the path
+fn encode_segment(segment: &str) -> String {
+    segment.replace('%', "%25").replace('/', "%2F")
+}
+
+// In queries '+' encodes a space, and invalid percent-encoded UTF-8 is replaced
+fn decode_query_component(component: &str) -> String {
+    let component = component.replace('+', " ");
+    String::from_utf8_lossy(&percent_decode(&component)).into_owned()
+}
+
+// Percent signs not followed by two hex digits are kept as they are
+// https://www.rfc-editor.org/rfc/rfc3986#section-2.1
+fn percent_decode(string: &str) -> Vec<u8> {
+    let bytes = string.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes
+            .get(index + 1..index + 3)
+            .filter(|hex_digits| hex_digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex_digits| {
+                let hex_digits = str::from_utf8(hex_digits).ok()?;
+                u8::from_str_radix(hex_digits, 16).ok()
+            });
+        match (bytes[index], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_and_query_are_split() {
+        let path = Path::new("/files/a.txt?download=1");
+
+        assert_eq!(&path, "/files/a.txt");
+        assert_eq!(path.query(), Some("download=1"));
+        assert_eq!(
+            path.strip_prefix("/files/")
+                .map(|path| path.as_str().to_string()),
+            Some("a.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn segments_are_split_before_being_decoded() {
+        for (target, expected_segments) in [
+            ("/", vec![""]),
+            ("/echo/a%2Fb?x=%2F", vec!["echo", "a/b"]),
+            ("/echo/a%20b/", vec!["echo", "a b", ""]),
+            ("//a/./b/../c/..", vec!["a", ""]),
+            ("/a/%2E%2E", vec![""]),
+            ("/files/a%2F..%2F..%2Fx", vec!["files", "a/../../x"]),
+            ("/100%25", vec!["100%"]),
+        ] {
+            assert_eq!(Path::new(target).segments(), expected_segments, "{target}");
+        }
+    }
+
+    #[test]
+    fn encoded_slashes_are_not_separators() {
+        let path = Path::new("/files/a%2F..%2F..%2Fx%25");
+
+        assert_eq!(path.as_str(), "/files/a%2F..%2F..%2Fx%25");
+        assert_eq!(
+            path.strip_prefix("/files/").map(|path| path.segments()),
+            Some(vec!["a/../../x%".to_string()])
+        );
+    }
+
+    #[test]
+    fn path_without_query() {
+        let path = Path::new("/echo/abc");
+
+        assert_eq!(path.query(), None);
+        assert_eq!(path.query_pairs().count(), 0);
+    }
+
+    #[test]
+    fn query_pairs_are_decoded() {
+        let path = Path::new("/search?q=a+b%21&empty=&flag&&bad=%zz%+1%4");
+
+        let query_pairs: Vec<_> = path.query_pairs().collect();
+
+        assert_eq!(
+            query_pairs,
+            [
+                ("q".to_string(), "a b!".to_string()),
+                ("empty".to_string(), String::new()),
+                ("flag".to_string(), String::new()),
+                ("bad".to_string(), "%zz% 1%4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_param_with_repeated_names() {
+        let path = Path::new("/?tag=a&other=b&tag=c");
+
+        assert_eq!(path.query_param("tag"), Some("a".to_string()));
+        assert_eq!(path.query_params("tag").collect::<Vec<_>>(), ["a", "c"]);
+        assert_eq!(path.query_param("missing"), None);
+    }
+
+    #[test]
+    fn path_is_percent_decoded() {
+        let path = Path::new("/echo/hello%20world%2Fagain?q=%20");
+
+        assert_eq!(&path, "/echo/hello world%2Fagain");
+        assert_eq!(path.query(), Some("q=%20"));
+        assert_eq!(path.raw(), "/echo/hello%20world%2Fagain?q=%20");
+    }
+
+    #[test]
+    fn path_is_normalised() {
+        let cases = [
+            ("/", "/"),
+            ("/a/./b", "/a/b"),
+            ("/a//c/../b", "/a/b"),
+            ("/a/b/", "/a/b/"),
+            ("/a/b/..", "/a/"),
+            ("/../../etc/passwd", "/etc/passwd"),
+            ("/files/%2E%2E/%2e%2e/secret", "/secret"),
+            ("//files///a", "/files/a"),
+        ];
+
+        for (target, expected_path) in cases {
+            assert_eq!(
+                Path::new(target).as_str(),
+                expected_path,
+                "Normalising {target}"
+            );
+        }
     }
 }
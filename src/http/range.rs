@@ -0,0 +1,203 @@
+use std::ops::RangeInclusive;
+
+// Requests for more ranges than this are served in full, since many small ranges cost far more to
+// send than the bytes they save
+const MAX_RANGES: usize = 16;
+
+// https://www.rfc-editor.org/rfc/rfc9110#section-14.1.1
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RangeSpec {
+    // "first-last", or "first-" when `last` is `None`
+    FromTo { first: u64, last: Option<u64> },
+    // "-length", the last `length` bytes
+    Suffix { length: u64 },
+}
+
+impl RangeSpec {
+    // Returns the bytes this spec covers in a representation `length` bytes long, or `None` if
+    // it covers none of them
+    // https://www.rfc-editor.org/rfc/rfc9110#section-14.1.2
+    pub fn resolve(self, length: u64) -> Option<RangeInclusive<u64>> {
+        let last_position = length.checked_sub(1)?;
+        match self {
+            Self::FromTo { first, .. } if first > last_position => None,
+            Self::FromTo { first, last } => {
+                let last = last.map_or(last_position, |last| last.min(last_position));
+                Some(first..=last)
+            }
+            Self::Suffix { length: 0 } => None,
+            Self::Suffix {
+                length: suffix_length,
+            } => Some(length.saturating_sub(suffix_length)..=last_position),
+        }
+    }
+}
+
+// Parses a `Range` header, like "bytes=0-99, -100". Returns `None` for ranges in other units or
+// malformed headers, which should be ignored.
+pub fn parse(range: &str) -> Option<Vec<RangeSpec>> {
+    let (unit, range_set) = range.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let range_specs = range_set
+        .split(',')
+        .map(str::trim)
+        .filter(|range_spec| !range_spec.is_empty())
+        .map(|range_spec| {
+            let (first, last) = range_spec.split_once('-')?;
+            match (first, last) {
+                ("", length) => Some(RangeSpec::Suffix {
+                    length: parse_position(length)?,
+                }),
+                (first, "") => Some(RangeSpec::FromTo {
+                    first: parse_position(first)?,
+                    last: None,
+                }),
+                (first, last) => {
+                    let (first, last) = (parse_position(first)?, parse_position(last)?);
+                    (first <= last).then_some(RangeSpec::FromTo {
+                        first,
+                        last: Some(last),
+                    })
+                }
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let is_valid = !range_specs.is_empty() && range_specs.len() <= MAX_RANGES;
+    is_valid.then_some(range_specs)
+}
+
+// Resolves each spec against a representation `length` bytes long, dropping those that cover none
+// of it. An empty result means the range is unsatisfiable. Overlapping and adjacent ranges are
+// coalesced, in order of position, so that no byte is sent twice however the specs overlap.
+// https://www.rfc-editor.org/rfc/rfc9110#section-14.3-7
+pub fn resolve(range_specs: &[RangeSpec], length: u64) -> Vec<RangeInclusive<u64>> {
+    let mut ranges: Vec<RangeInclusive<u64>> = range_specs
+        .iter()
+        .filter_map(|range_spec| range_spec.resolve(length))
+        .collect();
+    ranges.sort_unstable_by_key(|range| *range.start());
+
+    let mut coalesced: Vec<RangeInclusive<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                *last = *last.start()..=*last.end().max(range.end());
+            }
+            _ => coalesced.push(range),
+        }
+    }
+    coalesced
+}
+
+// The number of bytes in `range`
+pub fn length(range: &RangeInclusive<u64>) -> u64 {
+    range.end() - range.start() + 1
+}
+
+// The `Content-Range` of part of a representation
+// https://www.rfc-editor.org/rfc/rfc9110#section-14.4
+pub fn content_range(range: &RangeInclusive<u64>, length: u64) -> String {
+    format!("bytes {}-{}/{length}", range.start(), range.end())
+}
+
+// The `Content-Range` of a 416 response, which gives the length of the whole representation
+pub fn unsatisfied_content_range(length: u64) -> String {
+    format!("bytes */{length}")
+}
+
+// Positions too large for a `u64` are beyond the end of any file we could serve
+fn parse_position(position: &str) -> Option<u64> {
+    let is_digits = !position.is_empty() && position.bytes().all(|byte| byte.is_ascii_digit());
+    is_digits.then(|| position.parse().unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_specs() {
+        let range_specs = parse("bytes=0-99, 200-, -50,, 5-5");
+
+        assert_eq!(
+            range_specs,
+            Some(vec![
+                RangeSpec::FromTo {
+                    first: 0,
+                    last: Some(99)
+                },
+                RangeSpec::FromTo {
+                    first: 200,
+                    last: None
+                },
+                RangeSpec::Suffix { length: 50 },
+                RangeSpec::FromTo {
+                    first: 5,
+                    last: Some(5)
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_are_ignored() {
+        let too_many_ranges = format!("bytes={}", vec!["0-1"; MAX_RANGES + 1].join(","));
+
+        for range in [
+            "",
+            "bytes",
+            "bytes=",
+            "items=0-1",
+            "bytes=1-0",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=0-1;",
+            &too_many_ranges,
+        ] {
+            assert_eq!(parse(range), None, "Parsing {range}");
+        }
+    }
+
+    #[test]
+    fn resolve_range_specs() {
+        let range_specs =
+            parse("bytes=0-9, 95-200, -10, 100-, -0, 0-99999999999999999999999").unwrap();
+
+        let ranges = resolve(&range_specs, 100);
+
+        assert_eq!(ranges, [0..=99]);
+        assert_eq!(resolve(&range_specs, 0), []);
+        assert_eq!(resolve(&parse("bytes=-500").unwrap(), 100), [0..=99]);
+    }
+
+    #[test]
+    fn overlapping_and_adjacent_ranges_are_coalesced() {
+        let range_specs = parse("bytes=50-59, 0-9, 5-14, 15-19, 40-, -5").unwrap();
+
+        let ranges = resolve(&range_specs, 100);
+
+        assert_eq!(ranges, [0..=19, 40..=99]);
+        assert_eq!(
+            resolve(
+                &parse(&format!("bytes={}", ["0-"; MAX_RANGES].join(","))).unwrap(),
+                100
+            ),
+            [0..=99]
+        );
+        assert_eq!(
+            resolve(&parse("bytes=0-1, 3-4").unwrap(), 10),
+            [0..=1, 3..=4]
+        );
+    }
+
+    #[test]
+    fn format_content_ranges() {
+        assert_eq!(content_range(&(0..=9), 100), "bytes 0-9/100");
+        assert_eq!(unsatisfied_content_range(100), "bytes */100");
+    }
+}
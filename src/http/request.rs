@@ -1,27 +1,34 @@
-use nom::{bytes::complete::take, sequence::Tuple, IResult};
+use nom::{bytes::streaming::take, IResult};
 
 use crate::{
     body::Body,
-    headers::{HeaderName, Headers},
+    chunked,
+    error::Error,
+    headers::{HeaderName, HeaderValue, Headers},
+    limits::ParserLimits,
     method::Method,
     parsing_utils,
     path::Path,
+    request_target::RequestTarget,
+    router::Params,
     version::Version,
 };
 
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 pub struct Request {
     method: Method,
-    target: Path,
+    target: RequestTarget,
     version: Version,
     headers: Headers,
     body: Option<Body>,
+    // Set by the `Router` from the route the request matched
+    params: Params,
 }
 
 impl Request {
     pub fn new(
         method: Method,
-        target: Path,
+        target: RequestTarget,
         version: Version,
         headers: Headers,
         body: Option<Body>,
@@ -32,25 +39,40 @@ impl Request {
             version,
             headers,
             body,
+            params: Params::default(),
         }
     }
 
-    pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
-        let (remainder, (request_line, headers)) =
-            (RequestLine::parse, Headers::parse).parse(bytes)?;
+    /// Parses a complete request from the front of `bytes`.
+    ///
+    /// The parsers are streaming, so a request whose head or body has only been partially
+    /// received yields `nom::Err::Incomplete` rather than `nom::Err::Error`, letting callers
+    /// tell "read more bytes and try again" apart from a malformed request.
+    pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self, Error> {
+        Self::parse_with_limits(bytes, &ParserLimits::default())
+    }
 
-        let body = match headers.get(&HeaderName::ContentLength) {
-            None => None,
-            Some(header_value) => {
-                let content_length = header_value
-                    .as_usize()
-                    .expect("The value of Content-Length should be an integer");
-                let (_, body) = take(content_length)(remainder)?;
-                let body = body.to_owned();
-                let body = Body::OctetStream(body);
-                Some(body)
-            }
-        };
+    /// Like [`Request::parse`], but rejects requests exceeding `limits` with the matching error.
+    pub fn parse_with_limits<'a>(
+        bytes: &'a [u8],
+        limits: &ParserLimits,
+    ) -> IResult<&'a [u8], Self, Error> {
+        let (remainder, request_line) = parse_bounded(
+            RequestLine::parse,
+            bytes,
+            limits.max_request_line_length.saturating_add(2),
+            Error::TargetTooLong,
+        )?;
+        let (remainder, headers) = parse_bounded(
+            |bytes| with_error(Headers::parse(bytes), Error::MalformedHeader),
+            remainder,
+            limits.max_header_bytes,
+            Error::HeadersTooLarge,
+        )?;
+        if headers.len() > limits.max_header_count {
+            return Err(nom::Err::Error(Error::HeadersTooLarge));
+        }
+        let (remainder, body) = parse_body(remainder, &headers, limits)?;
 
         let request = Self::new(
             request_line.method,
@@ -66,54 +88,232 @@ impl Request {
         &self.headers
     }
 
-    pub fn target(&self) -> &Path {
+    // For middleware that rewrites requests before they're routed
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    pub fn target(&self) -> &RequestTarget {
         &self.target
     }
 
+    // The decoded and normalised path of an origin or absolute-form target, which is what
+    // requests should be routed on
+    pub fn path(&self) -> Option<&Path> {
+        self.target.path()
+    }
+
+    // The target exactly as the client sent it
+    pub fn raw_target(&self) -> String {
+        self.target.to_string()
+    }
+
     pub fn method(&self) -> Method {
         self.method
     }
 
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    // https://www.rfc-editor.org/rfc/rfc9112#section-9.3
+    pub fn is_persistent(&self) -> bool {
+        match self.headers.get_combined(&HeaderName::Connection) {
+            Some(connection) if connection.contains_token("close") => false,
+            Some(connection) if connection.contains_token("keep-alive") => true,
+            _ => self.version >= Version::OnePointOne,
+        }
+    }
+
     pub fn body(&self) -> Option<&Body> {
         self.body.as_ref()
     }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    // The value of a named parameter or wildcard in the route the request matched
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+    }
+
+    pub(crate) fn set_params(&mut self, params: Params) {
+        self.params = params;
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 struct RequestLine {
     pub method: Method,
-    pub target: Path,
+    pub target: RequestTarget,
     pub version: Version,
 }
 
 impl RequestLine {
-    pub fn new(method: Method, target: Path, version: Version) -> Self {
+    pub fn new(method: Method, target: RequestTarget, version: Version) -> Self {
         Self {
             method,
             target,
             version,
         }
     }
-    pub fn parse(bytes: &[u8]) -> IResult<&[u8], RequestLine> {
-        let (remainder, (method, _, target, _, version, _)) = (
-            Method::parse,
-            parsing_utils::space,
-            Path::parse,
-            parsing_utils::space,
-            Version::parse,
-            parsing_utils::crlf,
-        )
-            .parse(bytes)?;
+    pub fn parse(bytes: &[u8]) -> IResult<&[u8], RequestLine, Error> {
+        let (remainder, method) = match Method::parse(bytes) {
+            // A method we don't recognise is still a token, whereas anything else isn't HTTP
+            Err(nom::Err::Error(_)) if starts_with_token(bytes) => {
+                return Err(nom::Err::Error(Error::UnknownMethod));
+            }
+            result => with_error(result, Error::MalformedRequestLine)?,
+        };
+        let (remainder, _) =
+            with_error(parsing_utils::space(remainder), Error::MalformedRequestLine)?;
+        let (remainder, target) =
+            with_error(RequestTarget::parse(remainder), Error::InvalidTarget)?;
+        let (remainder, _) =
+            with_error(parsing_utils::space(remainder), Error::MalformedRequestLine)?;
+        let (remainder, version) = match Version::parse(remainder) {
+            Err(nom::Err::Error(_)) if remainder.starts_with(b"HTTP/") => {
+                return Err(nom::Err::Error(Error::UnsupportedVersion));
+            }
+            result => with_error(result, Error::InvalidVersion)?,
+        };
+        let (remainder, _) = with_error(parsing_utils::crlf(remainder), Error::InvalidVersion)?;
+
+        // Later versions aren't sent as text, and HTTP/0.9 requests have no version at all
+        if !matches!(version, Version::OnePointZero | Version::OnePointOne) {
+            return Err(nom::Err::Error(Error::UnsupportedVersion));
+        }
+
+        // Authority-form is only for CONNECT, which has no other form, and asterisk-form only
+        // for OPTIONS
+        // https://www.rfc-editor.org/rfc/rfc9112#section-3.2.3
+        let is_valid_form = match &target {
+            RequestTarget::Authority(_) => method == Method::Connect,
+            _ if method == Method::Connect => false,
+            RequestTarget::Asterisk => method == Method::Options,
+            RequestTarget::Origin(_) | RequestTarget::Absolute { .. } => true,
+        };
+        if !is_valid_form {
+            return Err(nom::Err::Error(Error::InvalidTarget));
+        }
+
         let request_line = RequestLine::new(method, target, version);
         Ok((remainder, request_line))
     }
 }
 
+// https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+fn parse_body<'a>(
+    bytes: &'a [u8],
+    headers: &Headers,
+    limits: &ParserLimits,
+) -> IResult<&'a [u8], Option<Body>, Error> {
+    // Each field line can list several codings, so they're only in order once combined
+    let transfer_encoding = headers.get_combined(&HeaderName::TransferEncoding);
+    let content_length = headers.get(&HeaderName::ContentLength);
+    // Repeated `Content-Length` headers that disagree make the body's length ambiguous
+    // https://www.rfc-editor.org/rfc/rfc9112#section-6.3-2.5
+    if headers
+        .get_all(&HeaderName::ContentLength)
+        .any(|header_value| Some(header_value) != content_length)
+    {
+        return Err(nom::Err::Error(Error::InvalidContentLength));
+    }
+
+    match (transfer_encoding, content_length) {
+        (None, None) => Ok((bytes, None)),
+        (None, Some(header_value)) => {
+            let content_length = parse_content_length(header_value)
+                .ok_or(nom::Err::Error(Error::InvalidContentLength))?;
+            if content_length > limits.max_body_size {
+                return Err(nom::Err::Error(Error::BodyTooLarge));
+            }
+            let (remainder, body) = take::<_, _, ()>(content_length)(bytes)
+                .map_err(|error| error.map(|_| Error::InvalidContentLength))?;
+            let body = body.to_owned();
+            let body = Body::OctetStream(body);
+            Ok((remainder, Some(body)))
+        }
+        (Some(header_value), None) => {
+            check_transfer_coding(&header_value)?;
+            let (remainder, body) =
+                chunked::parse(bytes, limits.max_body_size, limits.max_chunked_framing_size)?;
+            Ok((remainder, Some(body)))
+        }
+        // A request with both framing headers could be an attempt at request smuggling
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.1-15
+        (Some(_), Some(_)) => Err(nom::Err::Error(Error::ConflictingFraming)),
+    }
+}
+
+// Chunked is the only transfer coding we can decode. Unless it's the final coding, the body's
+// length can't be determined, so we mustn't guess where the next request starts.
+// https://www.rfc-editor.org/rfc/rfc9112#section-6.3-2.4
+fn check_transfer_coding(transfer_encoding: &HeaderValue) -> Result<(), nom::Err<Error>> {
+    let transfer_encoding = transfer_encoding.to_string();
+    let codings: Vec<&str> = transfer_encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    let is_chunked = |coding: &&str| coding.eq_ignore_ascii_case("chunked");
+    match codings.as_slice() {
+        [coding] if is_chunked(coding) => Ok(()),
+        [.., last] if !is_chunked(last) && codings.iter().any(is_chunked) => {
+            Err(nom::Err::Error(Error::ChunkedNotFinal))
+        }
+        _ => Err(nom::Err::Error(Error::UnsupportedTransferCoding)),
+    }
+}
+
+// `Content-Length` is only digits, whereas `usize::from_str` also accepts a leading '+'
+fn parse_content_length(header_value: &HeaderValue) -> Option<usize> {
+    let content_length = header_value.to_string();
+    if content_length.is_empty() || !content_length.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    header_value.as_usize()
+}
+
+fn starts_with_token(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .position(|byte| !parsing_utils::is_token_byte(*byte))
+        .is_some_and(|position| position > 0 && bytes[position] == b' ')
+}
+
+// Runs `parser` on at most the first `limit` bytes, failing with `error` if it would need more
+fn parse_bounded<'a, O>(
+    parser: impl FnOnce(&'a [u8]) -> IResult<&'a [u8], O, Error>,
+    bytes: &'a [u8],
+    limit: usize,
+    error: Error,
+) -> IResult<&'a [u8], O, Error> {
+    let is_truncated = bytes.len() > limit;
+    let bounded_bytes = &bytes[..bytes.len().min(limit)];
+    match parser(bounded_bytes) {
+        Ok((remainder, output)) => {
+            let consumed = bounded_bytes.len() - remainder.len();
+            Ok((&bytes[consumed..], output))
+        }
+        Err(nom::Err::Incomplete(_)) if is_truncated => Err(nom::Err::Error(error)),
+        result => result,
+    }
+}
+
+// Replaces a parser's nom error with our own, keeping `Incomplete` so callers know to read more
+fn with_error<I, O>(result: IResult<I, O>, error: Error) -> IResult<I, O, Error> {
+    result.map_err(|nom_error| nom_error.map(|_| error))
+}
+
 #[cfg(test)]
 mod tests {
     use std::str;
 
-    use crate::{method::Method, version::Version};
+    use proptest::prelude::*;
+
+    use crate::{method::Method, status_line::Status, version::Version};
 
     use super::*;
 
@@ -130,7 +330,7 @@ mod tests {
         let expected_headers = Headers::default();
         let expected_request = Request::new(
             Method::Get,
-            Path::new("/"),
+            RequestTarget::Origin(Path::new("/")),
             Version::OnePointOne,
             expected_headers,
             None,
@@ -154,7 +354,7 @@ mod tests {
         let expected_headers = make_expected_headers();
         let expected_request = Request::new(
             Method::Get,
-            Path::new("/index.html"),
+            RequestTarget::Origin(Path::new("/index.html")),
             Version::OnePointOne,
             expected_headers,
             None,
@@ -170,4 +370,367 @@ mod tests {
         assert!(remainder.is_empty());
         assert_eq!(request, expected_request);
     }
+
+    #[test]
+    fn parse_request_with_body() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET";
+
+        let (remainder, request) = Request::parse(bytes).expect("Request is complete");
+
+        assert_eq!(remainder, b"GET");
+        assert_eq!(request.body(), Some(&Body::from(b"abc".to_vec())));
+    }
+
+    #[test]
+    fn partial_request_is_incomplete() {
+        let bytes = b"GET /index.html HTTP/1.1\r\nHost: local";
+
+        let result = Request::parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn partial_body_is_incomplete() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nContent-Length: 5\r\n\r\nab";
+
+        let result = Request::parse(bytes);
+
+        assert!(matches!(result, Err(nom::Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn unknown_method_is_an_error() {
+        let bytes = b"FETCH / HTTP/1.1\r\n\r\n";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(result, Err(nom::Err::Error(Error::UnknownMethod)));
+    }
+
+    #[test]
+    fn http_1_1_requests_are_persistent_by_default() {
+        let (_, request) = Request::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+        assert!(request.is_persistent());
+    }
+
+    #[test]
+    fn http_1_0_requests_are_not_persistent_by_default() {
+        let (_, request) = Request::parse(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+
+        assert!(!request.is_persistent());
+    }
+
+    #[test]
+    fn connection_header_overrides_version_default() {
+        let (_, http_1_1_request) =
+            Request::parse(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (_, http_1_0_request) =
+            Request::parse(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+        assert!(!http_1_1_request.is_persistent());
+        assert!(http_1_0_request.is_persistent());
+    }
+
+    #[test]
+    fn parse_request_with_chunked_body() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+
+        let (remainder, request) = Request::parse(bytes).expect("Request is complete");
+
+        assert!(remainder.is_empty());
+        assert_eq!(request.body(), Some(&Body::from(b"abcde".to_vec())));
+    }
+
+    #[test]
+    fn both_framing_headers_is_an_error() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(result, Err(nom::Err::Error(Error::ConflictingFraming)));
+    }
+
+    #[test]
+    fn unsupported_transfer_coding_is_an_error() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(
+            result,
+            Err(nom::Err::Error(Error::UnsupportedTransferCoding))
+        );
+    }
+
+    #[test]
+    fn chunked_must_be_the_final_transfer_coding() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: gzip\r\n\r\n0\r\n\r\n";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(result, Err(nom::Err::Error(Error::ChunkedNotFinal)));
+        assert_eq!(Error::ChunkedNotFinal.status(), Status::BadRequest);
+
+        let bytes = b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(
+            result,
+            Err(nom::Err::Error(Error::UnsupportedTransferCoding))
+        );
+    }
+
+    #[test]
+    fn parse_request_with_unknown_headers() {
+        let bytes = b"GET / HTTP/1.1\r\nhost: localhost:4221\r\nAuthorization: Bearer abc\r\nCookie: a=1; b=2\r\n\r\n";
+
+        let (remainder, request) = Request::parse(bytes).expect("Request is valid");
+
+        assert!(remainder.is_empty());
+        assert_eq!(
+            request.headers().get(&HeaderName::from("authorization")),
+            Some(&HeaderValue::new("Bearer abc"))
+        );
+        assert_eq!(
+            request.headers().get(&HeaderName::from("cookie")),
+            Some(&HeaderValue::new("a=1; b=2"))
+        );
+    }
+
+    #[test]
+    fn conflicting_content_lengths_is_an_error() {
+        let bytes = b"POST /files/a HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd";
+
+        let result = Request::parse(bytes);
+
+        assert_eq!(result, Err(nom::Err::Error(Error::InvalidContentLength)));
+    }
+
+    #[test]
+    fn parse_errors_are_typed() {
+        let cases: [(&[u8], Error); 7] = [
+            (
+                b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03",
+                Error::MalformedRequestLine,
+            ),
+            (b"GET / HTTX/1.1\r\n\r\n", Error::InvalidVersion),
+            (b"GET / HTTP/2\r\n\r\n", Error::UnsupportedVersion),
+            (b"GET / HTTP/4.0\r\n\r\n", Error::UnsupportedVersion),
+            (
+                b"GET / HTTP/1.1\r\nBad Header\r\n\r\n",
+                Error::MalformedHeader,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: +3\r\n\r\nabc",
+                Error::InvalidContentLength,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+                Error::MalformedChunkedBody,
+            ),
+        ];
+
+        for (bytes, expected_error) in cases {
+            let result = Request::parse(bytes);
+
+            assert_eq!(
+                result,
+                Err(nom::Err::Error(expected_error)),
+                "Parsing {}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn raw_and_normalised_targets() {
+        let bytes = b"GET /files/./a%20b//c?x=1 HTTP/1.1\r\n\r\n";
+
+        let (_, request) = Request::parse(bytes).unwrap();
+
+        assert_eq!(request.path().map(Path::as_str), Some("/files/a b/c"));
+        assert_eq!(request.raw_target(), "/files/./a%20b//c?x=1");
+        assert_eq!(
+            Request::parse(b"GET /a%00 HTTP/1.1\r\n\r\n"),
+            Err(nom::Err::Error(Error::InvalidTarget))
+        );
+    }
+
+    #[test]
+    fn request_target_forms_must_match_the_method() {
+        let cases: [(&[u8], bool); 6] = [
+            (b"CONNECT www.example.org:443 HTTP/1.1\r\n\r\n", true),
+            (b"CONNECT / HTTP/1.1\r\n\r\n", false),
+            (b"GET www.example.org:443 HTTP/1.1\r\n\r\n", false),
+            (b"OPTIONS * HTTP/1.1\r\n\r\n", true),
+            (b"GET * HTTP/1.1\r\n\r\n", false),
+            (b"GET http://www.example.org/ HTTP/1.1\r\n\r\n", true),
+        ];
+
+        for (bytes, is_valid) in cases {
+            let result = Request::parse(bytes);
+
+            if is_valid {
+                assert!(result.is_ok(), "Parsing {}", String::from_utf8_lossy(bytes));
+            } else {
+                assert_eq!(
+                    result,
+                    Err(nom::Err::Error(Error::InvalidTarget)),
+                    "Parsing {}",
+                    String::from_utf8_lossy(bytes)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adversarial_requests_are_errors() {
+        let cases: [&[u8]; 10] = [
+            b"GET /\xff\xfe HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nUser-Agent: \xc3\x28\r\n\r\n",
+            b"GET / HTTP/1.1\r\nUser-Agent: a\0b\r\n\r\n",
+            b"GET / HTTP/1.1\r\n\xff: a\r\n\r\n",
+            b"GET / HTTP/1.1\r\n: a\r\n\r\n",
+            b"GET /\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 99999999999999999999999999\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffffffff\r\n",
+            b"\0\0\0\0 \0 \0\r\n\r\n",
+        ];
+
+        for bytes in cases {
+            let result = Request::parse(bytes);
+
+            assert!(
+                matches!(result, Err(nom::Err::Error(_))),
+                "Parsing {} gave {result:?}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn every_prefix_of_a_valid_request_is_incomplete() {
+        let requests: [&[u8]; 3] = [
+            b"GET /echo/abc HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: curl/7.64.1\r\n\r\n",
+            b"POST /files/a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+            b"POST /files/a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        ];
+
+        for bytes in requests {
+            for end in 0..bytes.len() {
+                let result = Request::parse(&bytes[..end]);
+
+                assert!(
+                    matches!(result, Err(nom::Err::Incomplete(_))),
+                    "Parsing {} gave {result:?}",
+                    String::from_utf8_lossy(&bytes[..end])
+                );
+            }
+            assert!(Request::parse(bytes).is_ok());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn parse_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let _ = Request::parse(&bytes);
+        }
+
+        #[test]
+        fn parse_never_panics_after_a_valid_prefix(
+            prefix in prop::sample::select(vec![
+                &b"GET / HTTP/1.1\r\n"[..],
+                b"GET / HTTP/1.1\r\nHost: a\r\n",
+                b"POST / HTTP/1.1\r\nContent-Length: ",
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+            ]),
+            suffix in proptest::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let bytes = [prefix, &suffix].concat();
+            let _ = Request::parse(&bytes);
+        }
+
+        #[test]
+        fn parse_never_panics_on_printable_input(input in "[ -~\r\n]{0,256}") {
+            let _ = Request::parse(input.as_bytes());
+        }
+    }
+
+    #[test]
+    fn requests_exceeding_limits_are_errors() {
+        let limits = ParserLimits {
+            max_request_line_length: 20,
+            max_header_bytes: 32,
+            max_header_count: 2,
+            max_body_size: 8,
+            max_chunked_framing_size: 16,
+        };
+        let cases: [(&[u8], Error); 7] = [
+            (b"GET /a-very-long-target", Error::TargetTooLong),
+            (
+                b"GET / HTTP/1.1\r\nUser-Agent: a-much-longer-user-agent",
+                Error::HeadersTooLarge,
+            ),
+            (
+                b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+                Error::HeadersTooLarge,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\n",
+                Error::BodyTooLarge,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n9\r\nabcdefghi",
+                Error::BodyTooLarge,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nabcde\r\n4\r\nfghi",
+                Error::BodyTooLarge,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1;a-long-extension\r\n",
+                Error::BodyTooLarge,
+            ),
+        ];
+
+        for (bytes, expected_error) in cases {
+            let result = Request::parse_with_limits(bytes, &limits);
+
+            assert_eq!(
+                result,
+                Err(nom::Err::Error(expected_error)),
+                "Parsing {}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn requests_at_limits_are_parsed() {
+        let bytes = b"POST /a HTTP/1.1\r\nContent-Length: 3\r\nHost: a\r\n\r\nabc";
+        let limits = ParserLimits {
+            max_request_line_length: 16,
+            max_header_bytes: 32,
+            max_header_count: 2,
+            max_body_size: 3,
+            max_chunked_framing_size: 16,
+        };
+
+        let (remainder, request) = Request::parse_with_limits(bytes, &limits).unwrap();
+
+        assert!(remainder.is_empty());
+        assert_eq!(request.body(), Some(&Body::from(b"abc".to_vec())));
+
+        // Chunk framing doesn't count towards the body's size
+        let bytes = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n1\r\nb\r\n1\r\nc\r\n0\r\n\r\n";
+
+        let (remainder, request) = Request::parse_with_limits(bytes, &limits).unwrap();
+
+        assert!(remainder.is_empty());
+        assert_eq!(request.body(), Some(&Body::from(b"abc".to_vec())));
+    }
 }
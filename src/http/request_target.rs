@@ -0,0 +1,241 @@
+use std::{fmt, str};
+
+use nom::{bytes, combinator, IResult};
+
+use crate::path::{self, Path};
+
+// https://www.rfc-editor.org/rfc/rfc9112#section-3.2
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum RequestTarget {
+    // "/where?q=now", the form used for requests sent directly to a server
+    Origin(Path),
+    // "http://www.example.org/where?q=now", the form used for requests sent to a proxy
+    Absolute {
+        scheme: String,
+        authority: String,
+        path: Path,
+    },
+    // "www.example.org:443", only used by CONNECT requests
+    Authority(String),
+    // "*", only used by server-wide OPTIONS requests
+    Asterisk,
+}
+
+impl RequestTarget {
+    pub fn parse(bytes: &[u8]) -> IResult<&[u8], Self> {
+        // Stopping at control characters means a request line missing its version, like
+        // "GET /\r\n", fails straight away rather than waiting for a space that never comes
+        let target_bytes =
+            bytes::streaming::take_while1(|byte: u8| byte != b' ' && !byte.is_ascii_control());
+        let target_string = combinator::map_res(target_bytes, str::from_utf8);
+        combinator::map_opt(target_string, Self::from_target)(bytes)
+    }
+
+    // The path requests should be routed on, which only origin and absolute-form targets have
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Origin(path) | Self::Absolute { path, .. } => Some(path),
+            Self::Authority(_) | Self::Asterisk => None,
+        }
+    }
+
+    fn from_target(target: &str) -> Option<Self> {
+        if target == "*" {
+            return Some(Self::Asterisk);
+        }
+        if target.starts_with('/') {
+            return path::is_valid_target(target).then(|| Self::Origin(Path::new(target)));
+        }
+
+        match target.split_once("://") {
+            Some((scheme, rest)) => {
+                let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+                let (authority, path_and_query) = rest.split_at(authority_end);
+                let is_valid = is_valid_scheme(scheme)
+                    && is_valid_authority(authority, false)
+                    && path::is_valid_target(path_and_query);
+                is_valid.then(|| Self::Absolute {
+                    scheme: scheme.to_string(),
+                    authority: authority.to_string(),
+                    path: Path::new(path_and_query),
+                })
+            }
+            None => is_valid_authority(target, true).then(|| Self::Authority(target.to_string())),
+        }
+    }
+}
+
+impl Default for RequestTarget {
+    fn default() -> Self {
+        Self::Origin(Path::new("/"))
+    }
+}
+
+// Writes the target as it was received
+impl fmt::Display for RequestTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Origin(path) => write!(f, "{}", path.raw()),
+            Self::Absolute {
+                scheme,
+                authority,
+                path,
+            } => write!(f, "{scheme}://{authority}{}", path.raw()),
+            Self::Authority(authority) => write!(f, "{authority}"),
+            Self::Asterisk => write!(f, "*"),
+        }
+    }
+}
+
+// https://www.rfc-editor.org/rfc/rfc3986#section-3.1
+fn is_valid_scheme(scheme: &str) -> bool {
+    let mut bytes = scheme.bytes();
+    bytes.next().is_some_and(|byte| byte.is_ascii_alphabetic())
+        && bytes.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'-' | b'.'))
+}
+
+// HTTP authorities are a host with an optional port, since userinfo is deprecated
+// https://www.rfc-editor.org/rfc/rfc9110#section-4.2.4
+fn is_valid_authority(authority: &str, is_port_required: bool) -> bool {
+    let (host, port) = match authority.rsplit_once(':') {
+        // The colons in an IPv6 address are inside the brackets
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (authority, None),
+    };
+    let is_valid_port = match port {
+        None => !is_port_required,
+        Some(port) => !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()),
+    };
+    is_valid_port && is_valid_host(host)
+}
+
+// https://www.rfc-editor.org/rfc/rfc3986#section-3.2.2
+fn is_valid_host(host: &str) -> bool {
+    match host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+    {
+        Some(ip_literal) => {
+            !ip_literal.is_empty()
+                && ip_literal
+                    .bytes()
+                    .all(|byte| byte.is_ascii_hexdigit() || matches!(byte, b':' | b'.'))
+        }
+        None => {
+            !host.is_empty()
+                && host.bytes().all(|byte| {
+                    byte.is_ascii_alphanumeric()
+                        || matches!(
+                            byte,
+                            b'-' | b'.'
+                                | b'_'
+                                | b'~'
+                                | b'%'
+                                | b'!'
+                                | b'$'
+                                | b'&'
+                                | b'\''
+                                | b'('
+                                | b')'
+                                | b'*'
+                                | b'+'
+                                | b','
+                                | b';'
+                                | b'='
+                        )
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_target(target: &str) -> Option<RequestTarget> {
+        let bytes = format!("{target} ");
+        RequestTarget::parse(bytes.as_bytes())
+            .ok()
+            .map(|(_, request_target)| request_target)
+    }
+
+    #[test]
+    fn origin_form() {
+        let request_target = parse_target("/echo/hello%20world?q=1").unwrap();
+
+        assert_eq!(
+            request_target.path().map(Path::as_str),
+            Some("/echo/hello world")
+        );
+        assert_eq!(request_target.to_string(), "/echo/hello%20world?q=1");
+    }
+
+    #[test]
+    fn absolute_form() {
+        let request_target = parse_target("http://www.example.org:8080/a/../b?q=1").unwrap();
+
+        let RequestTarget::Absolute {
+            scheme,
+            authority,
+            path,
+        } = &request_target
+        else {
+            panic!("Expected an absolute-form target, got {request_target:?}");
+        };
+        assert_eq!(scheme, "http");
+        assert_eq!(authority, "www.example.org:8080");
+        assert_eq!(path, "/b");
+        assert_eq!(path.query(), Some("q=1"));
+        assert_eq!(
+            request_target.to_string(),
+            "http://www.example.org:8080/a/../b?q=1"
+        );
+    }
+
+    #[test]
+    fn absolute_form_without_a_path() {
+        let request_target = parse_target("http://[::1]").unwrap();
+
+        assert_eq!(request_target.path().map(Path::as_str), Some("/"));
+        assert_eq!(request_target.to_string(), "http://[::1]");
+    }
+
+    #[test]
+    fn authority_form() {
+        assert_eq!(
+            parse_target("www.example.org:443"),
+            Some(RequestTarget::Authority("www.example.org:443".to_string()))
+        );
+        assert_eq!(
+            parse_target("[2001:db8::1]:443"),
+            Some(RequestTarget::Authority("[2001:db8::1]:443".to_string()))
+        );
+    }
+
+    #[test]
+    fn asterisk_form() {
+        assert_eq!(parse_target("*"), Some(RequestTarget::Asterisk));
+    }
+
+    #[test]
+    fn invalid_targets_are_errors() {
+        for target in [
+            "/a%2",
+            "/a%zz",
+            "/a%+1",
+            "/a%00b",
+            "/?q=%00",
+            "/%ff%fe",
+            "www.example.org",
+            "www.example.org:",
+            "www.example.org:https",
+            "user@www.example.org:443",
+            "1http://www.example.org/",
+            "http:///a",
+            "http://user@www.example.org/",
+            "**",
+        ] {
+            assert_eq!(parse_target(target), None, "Parsing {target}");
+        }
+    }
+}
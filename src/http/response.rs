@@ -1,8 +1,11 @@
 use std::fmt;
 
+use bytes::{BufMut, BytesMut};
+
 use crate::{
     body::Body,
-    headers::Headers,
+    error::Error,
+    headers::{HeaderValue, Headers},
     response_builder::ResponseBuilder,
     status_line::{Status, StatusLine},
 };
@@ -23,24 +26,164 @@ impl Response {
         }
     }
 
+    pub fn status(&self) -> &Status {
+        self.status_line.status()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn set_connection(mut self, connection: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_connection(connection);
+        self
+    }
+
+    pub fn set_keep_alive(mut self, keep_alive: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_keep_alive(keep_alive);
+        self
+    }
+
+    pub fn set_transfer_encoding(mut self, transfer_encoding: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_transfer_encoding(transfer_encoding);
+        self
+    }
+
+    pub fn body(&self) -> Option<&Body> {
+        self.body.as_ref()
+    }
+
+    // Serializes the response as it is sent to the client. Unlike `Display`, which is meant for
+    // logging, bodies that aren't valid UTF-8 are written verbatim.
+    pub fn encode(&self, buffer: &mut BytesMut) {
+        self.encode_head(buffer);
+        if let Some(body) = &self.body {
+            buffer.put(body.as_bytes());
+        }
+    }
+
+    // Serializes the status line and headers, as sent in response to a HEAD request
+    pub fn encode_head(&self, buffer: &mut BytesMut) {
+        buffer.put(self.status_line.to_string().as_bytes());
+        buffer.put(self.headers.to_string().as_bytes());
+        buffer.put(&b"\r\n"[..]);
+    }
+
+    // Responds to a request that couldn't be parsed, explaining why in the body
+    pub fn from_error(error: &Error) -> ResponseBuilder {
+        ResponseBuilder::default()
+            .set_status(error.status())
+            .set_body(format!("{error}\n"))
+    }
+
+    // For statuses without a constructor of their own, including custom ones
+    pub fn with_status(status: Status) -> ResponseBuilder {
+        ResponseBuilder::default().set_status(status)
+    }
+
     pub fn ok() -> ResponseBuilder {
-        ResponseBuilder::default().set_status(Status::Ok)
+        Self::with_status(Status::Ok)
     }
 
     pub fn created() -> ResponseBuilder {
-        ResponseBuilder::default().set_status(Status::Created)
+        Self::with_status(Status::Created)
+    }
+
+    pub fn accepted() -> ResponseBuilder {
+        Self::with_status(Status::Accepted)
+    }
+
+    pub fn no_content() -> ResponseBuilder {
+        Self::with_status(Status::NoContent)
+    }
+
+    pub fn moved_permanently(location: impl Into<HeaderValue>) -> ResponseBuilder {
+        Self::with_status(Status::MovedPermanently).set_location(location)
+    }
+
+    pub fn found(location: impl Into<HeaderValue>) -> ResponseBuilder {
+        Self::with_status(Status::Found).set_location(location)
+    }
+
+    pub fn see_other(location: impl Into<HeaderValue>) -> ResponseBuilder {
+        Self::with_status(Status::SeeOther).set_location(location)
+    }
+
+    pub fn not_modified() -> ResponseBuilder {
+        Self::with_status(Status::NotModified)
+    }
+
+    // Unlike 301 and 302, 307 and 308 keep the request's method when they're followed
+    pub fn temporary_redirect(location: impl Into<HeaderValue>) -> ResponseBuilder {
+        Self::with_status(Status::TemporaryRedirect).set_location(location)
+    }
+
+    pub fn permanent_redirect(location: impl Into<HeaderValue>) -> ResponseBuilder {
+        Self::with_status(Status::PermanentRedirect).set_location(location)
+    }
+
+    pub fn bad_request() -> ResponseBuilder {
+        Self::with_status(Status::BadRequest)
+    }
+
+    pub fn unauthorized() -> ResponseBuilder {
+        Self::with_status(Status::Unauthorized)
+    }
+
+    pub fn forbidden() -> ResponseBuilder {
+        Self::with_status(Status::Forbidden)
     }
 
     pub fn not_found() -> ResponseBuilder {
-        ResponseBuilder::default().set_status(Status::NotFound)
+        Self::with_status(Status::NotFound)
+    }
+
+    pub fn method_not_allowed() -> ResponseBuilder {
+        Self::with_status(Status::MethodNotAllowed)
+    }
+
+    pub fn conflict() -> ResponseBuilder {
+        Self::with_status(Status::Conflict)
+    }
+
+    pub fn gone() -> ResponseBuilder {
+        Self::with_status(Status::Gone)
+    }
+
+    pub fn precondition_failed() -> ResponseBuilder {
+        Self::with_status(Status::PreconditionFailed)
+    }
+
+    pub fn content_too_large() -> ResponseBuilder {
+        Self::with_status(Status::ContentTooLarge)
+    }
+
+    pub fn unsupported_media_type() -> ResponseBuilder {
+        Self::with_status(Status::UnsupportedMediaType)
+    }
+
+    pub fn too_many_requests() -> ResponseBuilder {
+        Self::with_status(Status::TooManyRequests)
     }
 
     pub fn internal_server_error() -> ResponseBuilder {
-        ResponseBuilder::default().set_status(Status::InternalServerError)
+        Self::with_status(Status::InternalServerError)
     }
 
-    pub fn bad_request() -> ResponseBuilder {
-        ResponseBuilder::default().set_status(Status::BadRequest)
+    pub fn not_implemented() -> ResponseBuilder {
+        Self::with_status(Status::NotImplemented)
+    }
+
+    pub fn bad_gateway() -> ResponseBuilder {
+        Self::with_status(Status::BadGateway)
+    }
+
+    pub fn service_unavailable() -> ResponseBuilder {
+        Self::with_status(Status::ServiceUnavailable)
+    }
+
+    pub fn gateway_timeout() -> ResponseBuilder {
+        Self::with_status(Status::GatewayTimeout)
     }
 }
 
@@ -65,7 +208,7 @@ mod tests {
         let default_response = Response::ok().build();
         assert_eq!(
             default_response.to_string(),
-            String::from("HTTP/1.1 200 OK\r\n\r\n")
+            String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
         );
     }
 
@@ -74,8 +217,30 @@ mod tests {
         let default_response = Response::not_found().build();
         assert_eq!(
             default_response.to_string(),
-            String::from("HTTP/1.1 404 Not Found\r\n\r\n")
+            String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn redirect_response() {
+        let response = Response::moved_permanently("/new").build();
+
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n"
         );
+        assert!(response.status().is_redirection());
+    }
+
+    #[test]
+    fn custom_status_response() {
+        let status = Status::custom(599, "Network Connect Timeout").unwrap();
+
+        let response = Response::with_status(status).build();
+
+        assert!(response
+            .to_string()
+            .starts_with("HTTP/1.1 599 Network Connect Timeout\r\n"));
     }
 
     #[test]
@@ -90,4 +255,49 @@ mod tests {
         let response = Response::ok().set_body("abc").build();
         assert_eq!(response, expected_response,);
     }
+
+    #[test]
+    fn encode_binary_response() {
+        let bytes = vec![0x00, 0x9f, 0x92, 0x96, 0xff];
+        let response = Response::ok().set_body(bytes.clone()).build();
+        let mut buffer = BytesMut::new();
+
+        response.encode(&mut buffer);
+
+        assert!(buffer.ends_with(&bytes));
+        assert!(buffer.starts_with(b"HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn encode_matches_display_for_text_response() {
+        let response = Response::ok().set_body("abc").build();
+        let mut buffer = BytesMut::new();
+
+        response.encode(&mut buffer);
+
+        assert_eq!(&buffer[..], response.to_string().as_bytes());
+    }
+
+    #[test]
+    fn response_serialisation_is_deterministic() {
+        let response = Response::ok().set_body("abc").build();
+
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 3\r\n\r\nabc"
+        );
+    }
+
+    #[test]
+    fn error_response_has_matching_status_and_diagnostic_body() {
+        let response = Response::from_error(&Error::UnsupportedVersion).build();
+
+        assert!(response
+            .to_string()
+            .starts_with("HTTP/1.1 505 HTTP Version Not Supported\r\n"));
+        assert_eq!(
+            response.body(),
+            Some(&Body::from("HTTP version is not supported\n"))
+        );
+    }
 }
@@ -1,6 +1,8 @@
 use crate::{
     body::Body,
-    headers::{ContentType, Headers},
+    compression::{self, ContentCoding},
+    conditional::EntityTag,
+    headers::{ContentType, HeaderName, HeaderValue, Headers},
     response::Response,
     status_line::{Status, StatusLine},
 };
@@ -14,6 +16,19 @@ pub struct ResponseBuilder {
 }
 
 impl ResponseBuilder {
+    // Responses built without a status are 200 OK
+    pub fn status(&self) -> &Status {
+        self.status.as_ref().unwrap_or(&Status::Ok)
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
     pub fn set_status(mut self, status: Status) -> Self {
         self.status = Some(status);
         self
@@ -22,29 +37,394 @@ impl ResponseBuilder {
     pub fn set_body(mut self, body: impl Into<Body>) -> Self {
         let body = body.into();
         let content_type = match &body {
-            Body::OctetStream(_) => ContentType::OctetStream,
+            Body::OctetStream(_) | Body::Stream(_) => ContentType::OctetStream,
             Body::PlainText(_) => ContentType::Text,
         };
-        let headers = self
-            .headers
-            .set_content_type(content_type)
-            .set_content_length(body.len());
+        let headers = self.headers.set_content_type(content_type);
+        // Streams of unknown length are framed by the `Connection` that writes them
+        let headers = match &body {
+            Body::Stream(body_stream) => match body_stream.length() {
+                None => headers,
+                Some(length) => headers.set_content_length(length),
+            },
+            _ => headers.set_content_length(body.len()),
+        };
         self.headers = headers;
         self.body = Some(body);
         self
     }
 
+    // Overrides the type `set_body` infers from the kind of body
+    pub fn set_content_type(mut self, content_type: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_content_type(content_type);
+        self
+    }
+
+    // Describes the length of the representation a bodiless response, like a 304, stands in for
+    pub fn set_content_length(mut self, content_length: usize) -> Self {
+        self.headers = self.headers.set_content_length(content_length);
+        self
+    }
+
+    pub fn set_accept_ranges(mut self, accept_ranges: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_accept_ranges(accept_ranges);
+        self
+    }
+
+    pub fn set_allow(mut self, allow: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_allow(allow);
+        self
+    }
+
+    pub fn set_content_range(mut self, content_range: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_content_range(content_range);
+        self
+    }
+
+    pub fn set_etag(mut self, etag: EntityTag) -> Self {
+        self.headers = self.headers.set_etag(etag);
+        self
+    }
+
+    pub fn set_location(mut self, location: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_location(location);
+        self
+    }
+
+    pub fn set_last_modified(mut self, last_modified: impl Into<HeaderValue>) -> Self {
+        self.headers = self.headers.set_last_modified(last_modified);
+        self
+    }
+
+    // Replaces any headers with the names of `headers`, keeping every value of repeated ones like
+    // `Set-Cookie`
+    pub fn set_headers(mut self, headers: Headers) -> Self {
+        for (header_name, _) in headers.iter() {
+            self.headers.remove(header_name);
+        }
+        self.headers.extend(headers);
+        self
+    }
+
+    // Compresses the body with the best coding in the request's `Accept-Encoding`, unless it's
+    // too short, streamed, of a type that is already compressed, or part of a range request
+    pub fn compress(mut self, accept_encoding: Option<&HeaderValue>) -> Self {
+        // Ranges are of the uncompressed representation
+        if self.status().code() == 206 {
+            return self;
+        }
+        // A 304 has nothing to compress, but must have the validators of the response it stands
+        // in for, which its headers describe. Without a `Content-Length`, that response is taken
+        // to have been streamed, so not compressed either.
+        if self.status().code() == 304 {
+            let content_length = self
+                .headers
+                .get(&HeaderName::ContentLength)
+                .and_then(|content_length| content_length.to_string().parse::<usize>().ok());
+            let Some(content_length) = content_length else {
+                return self;
+            };
+            if !self.is_compressible() {
+                return self;
+            }
+            vary_by_accept_encoding(&mut self.headers);
+            if content_length >= compression::MIN_COMPRESSIBLE_LENGTH
+                && accept_encoding.and_then(ContentCoding::negotiate).is_some()
+            {
+                weaken_etag(&mut self.headers);
+            }
+            return self;
+        }
+        let body = match &self.body {
+            Some(body @ (Body::PlainText(_) | Body::OctetStream(_))) => body,
+            _ => return self,
+        };
+        if !self.is_compressible() {
+            return self;
+        }
+
+        vary_by_accept_encoding(&mut self.headers);
+
+        if body.len() < compression::MIN_COMPRESSIBLE_LENGTH {
+            return self;
+        }
+        let Some(content_coding) = accept_encoding.and_then(ContentCoding::negotiate) else {
+            return self;
+        };
+        // Encoding into memory can't fail in practice, but if it does, the body is just as good
+        // sent as it is
+        let Ok(compressed) = content_coding.encode(body.as_bytes()) else {
+            return self;
+        };
+
+        self.headers = self
+            .headers
+            .set_content_encoding(content_coding)
+            .set_content_length(compressed.len());
+        weaken_etag(&mut self.headers);
+        self.body = Some(Body::OctetStream(compressed));
+        self
+    }
+
+    // Whether the type of the content is worth compressing, and it isn't already encoded
+    fn is_compressible(&self) -> bool {
+        let is_compressible_type = self
+            .headers
+            .content_type()
+            .map_or(true, compression::is_compressible);
+        is_compressible_type && self.headers.content_encoding().is_none()
+    }
+
     pub fn build(self) -> Response {
+        // A 304 never has content, and any `Content-Length` is that of the representation it
+        // stands in for
+        // https://www.rfc-editor.org/rfc/rfc9110#section-15.4.5
+        let is_not_modified = self.status().code() == 304;
+
         let status_line = match self.status {
             None => StatusLine::default(),
             Some(status) => StatusLine::make_http_1_1_status_line(status),
         };
 
+        if is_not_modified {
+            return Response::new(status_line, self.headers, None);
+        }
+
+        // 1xx and 204 responses can't have content, so must not have a `Content-Length` either
+        // https://www.rfc-editor.org/rfc/rfc9110#section-8.6-8
+        let status = status_line.status();
+        let may_have_content = !status.is_informational() && status.code() != 204;
         let headers = match self.body {
-            None => self.headers.set_content_length(0),
-            Some(_) => self.headers,
+            None if may_have_content => self.headers.set_content_length(0),
+            _ => self.headers,
         };
 
         Response::new(status_line, headers, self.body)
     }
 }
+
+// The body we send depends on `Accept-Encoding` even when we don't compress it, so caches
+// must not serve it to clients that accept different codings
+fn vary_by_accept_encoding(headers: &mut Headers) {
+    let varies_by_accept_encoding = headers
+        .get_all(&HeaderName::Vary)
+        .any(|vary| vary.contains_token("Accept-Encoding"));
+    if !varies_by_accept_encoding {
+        headers.append(HeaderName::Vary, HeaderValue::new("Accept-Encoding"));
+    }
+}
+
+// Compressed bytes differ from the ones a strong tag promises, but are equivalent
+fn weaken_etag(headers: &mut Headers) {
+    let strong_etag = headers
+        .etag()
+        .and_then(|etag| EntityTag::parse(&etag.to_string()))
+        .filter(|etag| !etag.is_weak());
+    if let Some(etag) = strong_etag {
+        headers.insert(HeaderName::ETag, EntityTag::weak(etag.opaque_tag()).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn compress_body_with_negotiated_coding() {
+        let text = "Hello, World! ".repeat(100);
+        let accept_encoding = HeaderValue::new("gzip");
+
+        let response = Response::ok()
+            .set_body(text.as_str())
+            .compress(Some(&accept_encoding))
+            .build();
+
+        let headers = response.headers();
+        let body = response.body().expect("Response has a body").as_bytes();
+        let mut decompressed = String::new();
+        GzDecoder::new(body)
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, text);
+        assert_eq!(headers.content_encoding(), Some(&HeaderValue::new("gzip")));
+        assert_eq!(
+            headers.content_type(),
+            Some(&HeaderValue::new("text/plain"))
+        );
+        assert_eq!(
+            headers.get(&HeaderName::ContentLength),
+            Some(&HeaderValue::new(body.len().to_string()))
+        );
+        assert_eq!(
+            headers.get(&HeaderName::Vary),
+            Some(&HeaderValue::new("Accept-Encoding"))
+        );
+    }
+
+    #[test]
+    fn compression_weakens_strong_etag() {
+        let text = "Hello, World! ".repeat(100);
+        let accept_encoding = HeaderValue::new("gzip");
+
+        let response = Response::ok()
+            .set_body(text.as_str())
+            .set_etag(EntityTag::strong("abc"))
+            .compress(Some(&accept_encoding))
+            .build();
+
+        assert_eq!(
+            response.headers().etag(),
+            Some(&HeaderValue::new("W/\"abc\""))
+        );
+
+        let response = ResponseBuilder::default()
+            .set_status(Status::NotModified)
+            .set_content_length(text.len())
+            .set_etag(EntityTag::strong("abc"))
+            .compress(Some(&accept_encoding))
+            .build();
+
+        assert_eq!(
+            response.headers().etag(),
+            Some(&HeaderValue::new("W/\"abc\""))
+        );
+    }
+
+    #[test]
+    fn not_modified_is_only_compressed_like_the_response_it_stands_in_for() {
+        let accept_encoding = HeaderValue::new("gzip");
+
+        for (content_type, content_length, varies, is_weakened) in [
+            ("text/plain", Some(1024), true, true),
+            ("text/plain", Some(3), true, false),
+            ("text/plain", None, false, false),
+            ("image/png", Some(1024), false, false),
+        ] {
+            let response_builder = ResponseBuilder::default()
+                .set_status(Status::NotModified)
+                .set_content_type(content_type)
+                .set_etag(EntityTag::strong("abc"));
+            let response_builder = match content_length {
+                Some(content_length) => response_builder.set_content_length(content_length),
+                None => response_builder,
+            };
+
+            let response = response_builder.compress(Some(&accept_encoding)).build();
+
+            let headers = response.headers();
+            assert_eq!(
+                headers.get(&HeaderName::Vary).is_some(),
+                varies,
+                "{content_type} of length {content_length:?}"
+            );
+            assert_eq!(
+                headers.etag() == Some(&HeaderValue::new("W/\"abc\"")),
+                is_weakened,
+                "{content_type} of length {content_length:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn not_modified_has_no_body() {
+        let response = ResponseBuilder::default()
+            .set_status(Status::NotModified)
+            .set_body("abc")
+            .set_etag(EntityTag::strong("abc"))
+            .build();
+
+        assert_eq!(response.body(), None);
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 304 Not Modified\r\nContent-Type: text/plain\r\nContent-Length: 3\r\nETag: \"abc\"\r\n\r\n"
+        );
+
+        let response = ResponseBuilder::default()
+            .set_status(Status::custom(304, "Unchanged").unwrap())
+            .set_body("abc")
+            .build();
+
+        assert_eq!(response.body(), None);
+    }
+
+    #[test]
+    fn do_not_compress_custom_partial_content() {
+        let text = "Hello, World! ".repeat(100);
+        let accept_encoding = HeaderValue::new("gzip");
+
+        let response = ResponseBuilder::default()
+            .set_status(Status::custom(206, "Some Content").unwrap())
+            .set_body(text.as_str())
+            .compress(Some(&accept_encoding))
+            .build();
+
+        assert_eq!(response.headers().content_encoding(), None);
+    }
+
+    #[test]
+    fn no_content_length_without_content() {
+        for status in [
+            Status::NoContent,
+            Status::Continue,
+            Status::custom(204, "Nothing").unwrap(),
+        ] {
+            let response = ResponseBuilder::default().set_status(status).build();
+
+            assert_eq!(response.headers().get(&HeaderName::ContentLength), None);
+        }
+
+        let response = ResponseBuilder::default()
+            .set_status(Status::Created)
+            .build();
+
+        assert_eq!(
+            response.headers().get(&HeaderName::ContentLength),
+            Some(&HeaderValue::new("0"))
+        );
+    }
+
+    #[test]
+    fn do_not_compress_short_body() {
+        let accept_encoding = HeaderValue::new("gzip");
+
+        let response = Response::ok()
+            .set_body("abc")
+            .compress(Some(&accept_encoding))
+            .build();
+
+        assert_eq!(response.headers().content_encoding(), None);
+        assert_eq!(response.body(), Some(&Body::from("abc")));
+    }
+
+    #[test]
+    fn do_not_compress_without_accept_encoding() {
+        let text = "Hello, World! ".repeat(100);
+
+        let response = Response::ok()
+            .set_body(text.as_str())
+            .compress(None)
+            .build();
+
+        assert_eq!(response.headers().content_encoding(), None);
+        assert_eq!(
+            response.headers().get(&HeaderName::Vary),
+            Some(&HeaderValue::new("Accept-Encoding"))
+        );
+    }
+
+    #[test]
+    fn do_not_compress_already_compressed_type() {
+        let accept_encoding = HeaderValue::new("gzip");
+        let mut response_builder = Response::ok().set_body(vec![0u8; 1024]);
+        response_builder.headers = response_builder.headers.set_content_type("image/png");
+
+        let response = response_builder.compress(Some(&accept_encoding)).build();
+
+        assert_eq!(response.headers().content_encoding(), None);
+        assert_eq!(response.headers().get(&HeaderName::Vary), None);
+    }
+}
@@ -0,0 +1,612 @@
+use std::future;
+
+use crate::{
+    extract::SharedState,
+    handler::{BoxFuture, Handler},
+    method::Method,
+    middleware::{Middleware, Next},
+    request::Request,
+    request_target::RequestTarget,
+    response::Response,
+    response_builder::ResponseBuilder,
+};
+
+type BoxHandler =
+    Box<dyn Fn(Request, &SharedState) -> BoxFuture<'static, ResponseBuilder> + Send + Sync>;
+
+// Dispatches each request to the handler of the most specific route matching its method and path.
+// Patterns are made of segments that are either static, like "users", a named parameter matching
+// any one segment, like ":id", or a wildcard matching the rest of the path, like "*rest", e.g.
+// "/users/:id/files/*rest".
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: BoxHandler,
+    state: SharedState,
+    layers: Vec<Box<dyn Middleware>>,
+}
+
+impl Router {
+    // Responds 404 to requests that no route matches unless `set_fallback` is called
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            fallback: boxed(|| async { Response::not_found() }),
+            state: SharedState::default(),
+            layers: Vec::new(),
+        }
+    }
+
+    // Panics if `pattern` is invalid, since routes are fixed before the server starts
+    pub fn route<Args>(
+        mut self,
+        method: Method,
+        pattern: &str,
+        handler: impl Handler<Args>,
+    ) -> Self {
+        self.add_route(Some(method), pattern, boxed(handler));
+        self
+    }
+
+    pub fn get<Args>(self, pattern: &str, handler: impl Handler<Args>) -> Self {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post<Args>(self, pattern: &str, handler: impl Handler<Args>) -> Self {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put<Args>(self, pattern: &str, handler: impl Handler<Args>) -> Self {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn delete<Args>(self, pattern: &str, handler: impl Handler<Args>) -> Self {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    // Matches requests with any method, but a route for the request's method with an equally
+    // specific pattern takes priority
+    pub fn any<Args>(mut self, pattern: &str, handler: impl Handler<Args>) -> Self {
+        self.add_route(None, pattern, boxed(handler));
+        self
+    }
+
+    // Handles requests for paths that no route matches, and those whose targets have no path
+    // other than `OPTIONS *`
+    pub fn set_fallback<Args>(mut self, handler: impl Handler<Args>) -> Self {
+        self.fallback = boxed(handler);
+        self
+    }
+
+    // Shares `state` with every handler, which extract it by its type with `State<S>`. Adding
+    // state of a type the router already has replaces it.
+    pub fn set_state<S: Clone + Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state.insert(state);
+        self
+    }
+
+    // Wraps the handling of every request, including those the router responds to itself, in
+    // `middleware`. Layers run in the order they're added, so the first is the outermost: it sees
+    // the request first and the response last.
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.layers.push(Box::new(middleware));
+        self
+    }
+
+    // Runs `request` through the layers and then the router
+    pub fn handle(&self, request: Request) -> BoxFuture<'_, ResponseBuilder> {
+        Next::new(&self.layers, self).run(request)
+    }
+
+    // When several routes match, static segments take priority over parameters, which take
+    // priority over wildcards, comparing from the start of the path. Routes that are just as
+    // specific are tried in the order they were added. HEAD requests are handled by GET routes
+    // unless there's a HEAD route, and the `Connection` leaves out the body.
+    //
+    // Requests for a path that only has routes for other methods get 405, unless they're OPTIONS
+    // requests, which are answered with the methods the path allows. `OPTIONS *` is answered
+    // with every method any route allows.
+    // https://www.rfc-editor.org/rfc/rfc9110#section-9.3.7
+    //
+    // The route is chosen before returning, so the future doesn't borrow the router.
+    pub(crate) fn call_route(&self, mut request: Request) -> BoxFuture<'static, ResponseBuilder> {
+        match self.dispatch(&mut request) {
+            Ok(handler) => handler(request, &self.state),
+            Err(response) => Box::pin(future::ready(response)),
+        }
+    }
+
+    // Finds the handler for `request`, setting its params, or the response the router gives itself
+    fn dispatch(&self, request: &mut Request) -> Result<&BoxHandler, ResponseBuilder> {
+        let Some(path) = request.path() else {
+            return match (request.method(), request.target()) {
+                (Method::Options, RequestTarget::Asterisk) => {
+                    Err(Response::ok().set_allow(allow(self.routes.iter())))
+                }
+                _ => Ok(&self.fallback),
+            };
+        };
+        let method = request.method();
+        let path_segments = path.segments();
+        let matched = self
+            .routes
+            .iter()
+            .filter_map(|route| Some((route, route.method_rank(method)?)))
+            .filter_map(|(route, method_rank)| {
+                let params = route.pattern.matches(&path_segments)?;
+                Some((route, method_rank, params))
+            })
+            .min_by_key(|(route, method_rank, _)| (&route.pattern.priority, *method_rank))
+            .map(|(route, _, params)| (route, params));
+
+        if let Some((route, params)) = matched {
+            request.set_params(params);
+            return Ok(&route.handler);
+        }
+
+        let mut path_routes = self
+            .routes
+            .iter()
+            .filter(|route| route.pattern.matches(&path_segments).is_some())
+            .peekable();
+        if path_routes.peek().is_none() {
+            return Ok(&self.fallback);
+        }
+        let allow = allow(path_routes);
+        match method {
+            Method::Options => Err(Response::ok().set_allow(allow)),
+            _ => Err(Response::method_not_allowed().set_allow(allow)),
+        }
+    }
+
+    fn add_route(&mut self, method: Option<Method>, pattern: &str, handler: BoxHandler) {
+        let pattern = Pattern::parse(pattern)
+            .unwrap_or_else(|error| panic!("Invalid route pattern {pattern:?}: {error}"));
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler,
+        });
+    }
+}
+
+// The `Allow` header listing the methods of `routes`, HEAD for GET routes, and OPTIONS, which is
+// always answered
+// https://www.rfc-editor.org/rfc/rfc9110#section-10.2.1
+fn allow<'a>(routes: impl Iterator<Item = &'a Route>) -> String {
+    let mut methods: Vec<Method> = routes
+        .flat_map(|route| match route.method {
+            Some(Method::Get) => vec![Method::Get, Method::Head],
+            Some(method) => vec![method],
+            None => Method::ALL.to_vec(),
+        })
+        .chain([Method::Options])
+        .collect();
+    methods.sort_unstable();
+    methods.dedup();
+    methods
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn boxed<Args>(handler: impl Handler<Args>) -> BoxHandler {
+    Box::new(move |request, state| handler.call(request, state))
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Route {
+    // `None` matches any method
+    method: Option<Method>,
+    pattern: Pattern,
+    handler: BoxHandler,
+}
+
+impl Route {
+    // How closely the route's method matches `method`, lower being closer, or `None` if it
+    // doesn't match
+    // https://www.rfc-editor.org/rfc/rfc9110#section-9.3.2
+    fn method_rank(&self, method: Method) -> Option<u8> {
+        match (self.method, method) {
+            (Some(route_method), method) if route_method == method => Some(0),
+            (Some(Method::Get), Method::Head) => Some(1),
+            (None, _) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Segment {
+    // Lower is more specific
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Static(_) => 0,
+            Self::Param(_) => 1,
+            Self::Wildcard(_) => 2,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Pattern {
+    segments: Vec<Segment>,
+    // The rank of each segment, which orders patterns by how specific they are
+    priority: Vec<u8>,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self, &'static str> {
+        let pattern = pattern
+            .strip_prefix('/')
+            .ok_or("Patterns must start with a slash")?;
+        let mut segments = Vec::new();
+        for segment in pattern.split('/') {
+            if matches!(segments.last(), Some(Segment::Wildcard(_))) {
+                return Err("Wildcards must be the last segment");
+            }
+            let segment = if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Static(segment.to_string())
+            };
+            segments.push(segment);
+        }
+
+        let mut names: Vec<&str> = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Param(name) | Segment::Wildcard(name) => Some(name.as_str()),
+                Segment::Static(_) => None,
+            })
+            .collect();
+        if names.iter().any(|name| name.is_empty()) {
+            return Err("Parameters and wildcards must be named");
+        }
+        names.sort_unstable();
+        if names.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err("Parameter names must be unique");
+        }
+
+        let priority = segments.iter().map(Segment::rank).collect();
+        Ok(Self { segments, priority })
+    }
+
+    // Returns the values of the pattern's parameters if the decoded segments of a path match it.
+    // Parameters match one non-empty segment, and wildcards the rest of the path after their slash.
+    fn matches(&self, path_segments: &[String]) -> Option<Params> {
+        let mut path_segments = path_segments.iter().map(String::as_str);
+        let mut params = Params::default();
+        for segment in &self.segments {
+            match segment {
+                Segment::Static(expected) => {
+                    if path_segments.next()? != expected {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = path_segments.next().filter(|value| !value.is_empty())?;
+                    params.0.push((name.clone(), value.to_string()));
+                }
+                Segment::Wildcard(name) => {
+                    let rest: Vec<&str> = path_segments.by_ref().collect();
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    params.0.push((name.clone(), rest.join("/")));
+                }
+            }
+        }
+        match path_segments.next() {
+            None => Some(params),
+            Some(_) => None,
+        }
+    }
+}
+
+// The values of the named parameters and wildcard in the pattern of the route a request matched
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        extract::State,
+        headers::{HeaderName, HeaderValue},
+        path::Path,
+        status_line::Status,
+        version::Version,
+    };
+
+    use super::*;
+
+    fn request(method: Method, path: &str) -> Request {
+        let target = RequestTarget::Origin(Path::new(path));
+        Request::new(method, target, Version::default(), Default::default(), None)
+    }
+
+    // Responds with the name of the route and the params it matched
+    fn named(name: &'static str) -> impl Handler<Request> {
+        move |request: Request| async move {
+            let params: Vec<String> = request
+                .params()
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            format!("{name} {}", params.join(" "))
+        }
+    }
+
+    async fn body(router: &Router, method: Method, path: &str) -> String {
+        let response = router.handle(request(method, path)).await.build();
+        match response.body() {
+            Some(body) => String::from_utf8_lossy(body.as_bytes()).trim().to_string(),
+            None => response.status().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn match_static_params_and_wildcards() {
+        let router = Router::new()
+            .get("/", named("root"))
+            .get("/users/:id", named("user"))
+            .get("/users/:id/files/*rest", named("files"));
+
+        assert_eq!(body(&router, Method::Get, "/").await, "root");
+        assert_eq!(body(&router, Method::Get, "/users/42").await, "user id=42");
+        assert_eq!(
+            body(&router, Method::Get, "/users/42/files/a/b.txt").await,
+            "files id=42 rest=a/b.txt"
+        );
+        assert_eq!(
+            body(&router, Method::Get, "/users/42/files/").await,
+            "files id=42 rest="
+        );
+        for path in [
+            "/users",
+            "/users/",
+            "/users/42/",
+            "/users/42/files",
+            "/other",
+        ] {
+            assert_eq!(
+                body(&router, Method::Get, path).await,
+                "404 Not Found",
+                "{path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn params_are_decoded() {
+        let router = Router::new().get("/echo/:text", named("echo"));
+
+        assert_eq!(
+            body(&router, Method::Get, "/echo/hello%20world").await,
+            "echo text=hello world"
+        );
+        assert_eq!(
+            body(&router, Method::Get, "/echo/a%2Fb").await,
+            "echo text=a/b"
+        );
+    }
+
+    #[tokio::test]
+    async fn most_specific_route_takes_priority() {
+        let router = Router::new()
+            .get("/users/*rest", named("wildcard"))
+            .get("/users/:id", named("param"))
+            .get("/users/me", named("static"))
+            .get("/users/:id/:field", named("two params"));
+
+        assert_eq!(body(&router, Method::Get, "/users/me").await, "static");
+        assert_eq!(body(&router, Method::Get, "/users/42").await, "param id=42");
+        assert_eq!(
+            body(&router, Method::Get, "/users/42/name").await,
+            "two params id=42 field=name"
+        );
+        assert_eq!(
+            body(&router, Method::Get, "/users/42/name/x").await,
+            "wildcard rest=42/name/x"
+        );
+    }
+
+    #[tokio::test]
+    async fn routes_are_dispatched_by_method() {
+        let router = Router::new()
+            .get("/items", named("list"))
+            .post("/items", named("create"))
+            .any("/items", named("any"))
+            .delete("/items/:id", named("delete"));
+
+        assert_eq!(body(&router, Method::Get, "/items").await, "list");
+        assert_eq!(body(&router, Method::Post, "/items").await, "create");
+        assert_eq!(body(&router, Method::Put, "/items").await, "any");
+        assert_eq!(
+            body(&router, Method::Delete, "/items/1").await,
+            "delete id=1"
+        );
+        assert_eq!(
+            body(&router, Method::Get, "/items/1").await,
+            "405 Method Not Allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn head_requests_use_get_routes() {
+        let router = Router::new()
+            .get("/items", named("get items"))
+            .get("/custom", named("get custom"))
+            .route(Method::Head, "/custom", named("head custom"))
+            .any("/any", named("any"))
+            .get("/any", named("get any"));
+
+        assert_eq!(body(&router, Method::Head, "/items").await, "get items");
+        assert_eq!(body(&router, Method::Head, "/custom").await, "head custom");
+        assert_eq!(body(&router, Method::Head, "/any").await, "get any");
+    }
+
+    #[tokio::test]
+    async fn other_methods_are_not_allowed() {
+        let router = Router::new()
+            .get("/files/*path", named("read"))
+            .post("/files/*path", named("create"))
+            .put("/files/:name", named("replace"));
+
+        let response = router
+            .handle(request(Method::Delete, "/files/x"))
+            .await
+            .build();
+
+        assert_eq!(response.status(), &Status::MethodNotAllowed);
+        assert_eq!(
+            response.headers().get(&HeaderName::Allow),
+            Some(&HeaderValue::new("GET, HEAD, POST, PUT, OPTIONS"))
+        );
+
+        let response = router
+            .handle(request(Method::Delete, "/files/a/b"))
+            .await
+            .build();
+
+        assert_eq!(
+            response.headers().get(&HeaderName::Allow),
+            Some(&HeaderValue::new("GET, HEAD, POST, OPTIONS"))
+        );
+        assert_eq!(
+            body(&router, Method::Delete, "/other").await,
+            "404 Not Found"
+        );
+    }
+
+    #[tokio::test]
+    async fn options_are_answered_from_the_routes() {
+        let router = Router::new()
+            .get("/items", named("list"))
+            .delete("/items/:id", named("delete"))
+            .route(Method::Options, "/custom", named("custom options"));
+
+        let response = router
+            .handle(request(Method::Options, "/items"))
+            .await
+            .build();
+
+        assert_eq!(response.status(), &Status::Ok);
+        assert_eq!(
+            response.headers().get(&HeaderName::Allow),
+            Some(&HeaderValue::new("GET, HEAD, OPTIONS"))
+        );
+        assert_eq!(
+            body(&router, Method::Options, "/custom").await,
+            "custom options"
+        );
+        assert_eq!(
+            body(&router, Method::Options, "/missing").await,
+            "404 Not Found"
+        );
+
+        let request = Request::new(
+            Method::Options,
+            RequestTarget::Asterisk,
+            Version::default(),
+            Default::default(),
+            None,
+        );
+        let response = router.handle(request).await.build();
+
+        assert_eq!(
+            response.headers().get(&HeaderName::Allow),
+            Some(&HeaderValue::new("GET, HEAD, DELETE, OPTIONS"))
+        );
+    }
+
+    #[tokio::test]
+    async fn unmatched_requests_use_the_fallback() {
+        let router = Router::new()
+            .get("/", named("root"))
+            .set_fallback(named("fallback"));
+
+        assert_eq!(body(&router, Method::Get, "/missing").await, "fallback");
+
+        let request = Request::new(
+            Method::Connect,
+            RequestTarget::Authority("example.com:443".to_string()),
+            Version::default(),
+            Default::default(),
+            None,
+        );
+        let response = router.handle(request).await.build();
+        assert_eq!(
+            response.body().map(|body| body.as_bytes()),
+            Some(&b"fallback "[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn handlers_share_the_router_state() {
+        let router = Router::new().set_state(String::from("shared")).get(
+            "/state/:id",
+            |State(value): State<String>, params: Params| async move {
+                format!("{value} {}", params.get("id").unwrap_or_default())
+            },
+        );
+
+        assert_eq!(body(&router, Method::Get, "/state/1").await, "shared 1");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in [
+            "users",
+            "/files/*rest/more",
+            "/users/:",
+            "/files/*",
+            "/users/:id/:id",
+            "/:name/*name",
+        ] {
+            assert!(Pattern::parse(pattern).is_err(), "Parsing {pattern}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid route pattern")]
+    fn adding_an_invalid_pattern_panics() {
+        let _ = Router::new().get("no-slash", named("invalid"));
+    }
+}
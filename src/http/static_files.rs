@@ -0,0 +1,903 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, Metadata},
+    hash::{BuildHasher, Hasher, RandomState},
+    io::{self, Read, Seek, SeekFrom},
+    panic,
+    path::{self, PathBuf},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, ReadBuf};
+
+use crate::{
+    body::BodyStream,
+    compression,
+    conditional::{self, EntityTag, Precondition},
+    file_root::{FileRoot, FileRootError},
+    headers::{HeaderValue, Headers},
+    http_date,
+    method::Method,
+    mime,
+    path::Path,
+    range,
+    response::Response,
+    response_builder::ResponseBuilder,
+    status_line::Status,
+};
+
+const INDEX_FILE_NAME: &str = "index.html";
+// https://mimesniff.spec.whatwg.org/#reading-the-resource-header
+const SNIFF_LENGTH: u64 = 1445;
+// Files up to this size are read into memory so that they can be compressed. Larger ones are
+// streamed from disk, as are those sent in response to HEAD that wouldn't be compressed, so that
+// the `Connection`, which never writes the body of a response to HEAD, doesn't read them at all.
+const MAX_BUFFERED_LENGTH: u64 = 1024 * 1024;
+
+// Serves the files under a `FileRoot` at the paths under a mount point, so with the mount point
+// "/static/" a request for "/static/css/site.css" gets the file "css/site.css"
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StaticFiles {
+    file_root: FileRoot,
+    mount_point: String,
+    sniffs_content: bool,
+}
+
+impl StaticFiles {
+    // Mounted at "/" unless `set_mount_point` is called
+    pub fn new(file_root: FileRoot) -> Self {
+        Self {
+            file_root,
+            mount_point: "/".to_string(),
+            sniffs_content: false,
+        }
+    }
+
+    pub fn set_mount_point(mut self, mount_point: impl Into<String>) -> Self {
+        let mut mount_point = mount_point.into();
+        if !mount_point.starts_with('/') {
+            mount_point.insert(0, '/');
+        }
+        if !mount_point.ends_with('/') {
+            mount_point.push('/');
+        }
+        self.mount_point = mount_point;
+        self
+    }
+
+    // Whether files without a known extension have their type guessed from their content, rather
+    // than being sent as "application/octet-stream"
+    pub fn set_sniffs_content(mut self, sniffs_content: bool) -> Self {
+        self.sniffs_content = sniffs_content;
+        self
+    }
+
+    pub fn file_root(&self) -> &FileRoot {
+        &self.file_root
+    }
+
+    pub fn mount_point(&self) -> &str {
+        &self.mount_point
+    }
+
+    // The decoded segments of the requested file's path relative to the file root, or `None` if
+    // `path` isn't under the mount point
+    pub fn relative_segments(&self, path: &Path) -> Option<Vec<String>> {
+        let mount_point_segments = Path::new(self.mount_point.as_str()).segments();
+        // The mount point ends in a slash, so has an empty last segment
+        let mount_point_segments = &mount_point_segments[..mount_point_segments.len() - 1];
+        let segments = path.segments();
+        let relative_segments = segments.strip_prefix(mount_point_segments)?;
+        Some(relative_segments.to_vec())
+    }
+
+    // Responds with the requested file, or `index.html` if a directory is requested, redirecting
+    // to its path with a trailing slash if it has none, or just the parts of the file in the
+    // request's `Range` header, or 304 or 412 if the request's preconditions say to. Returns `None` if `path` isn't under the mount point, and an error if the file can't
+    // be found or read. The file is found and read on a thread where blocking is allowed, so slow
+    // disks don't hold up other connections.
+    pub async fn serve(
+        &self,
+        method: &Method,
+        path: &Path,
+        request_headers: &Headers,
+    ) -> Option<Result<ResponseBuilder, FileRootError>> {
+        let relative_segments = self.relative_segments(path)?;
+        let raw_path = path
+            .raw()
+            .split_once('?')
+            .map_or(path.raw(), |(raw_path, _)| raw_path);
+        let directory_location = match path.query() {
+            Some(query) => format!("{raw_path}/?{query}"),
+            None => format!("{raw_path}/"),
+        };
+        let static_files = self.clone();
+        let method = *method;
+        let request_headers = request_headers.clone();
+        let response_builder = tokio::task::spawn_blocking(move || {
+            match static_files.resolve(&relative_segments)? {
+                Resolved::File(file_path) => {
+                    let response_builder =
+                        static_files.respond_with_file(&method, &file_path, &request_headers)?;
+                    Ok(response_builder)
+                }
+                Resolved::DirectoryWithoutSlash => {
+                    Ok(Response::moved_permanently(directory_location))
+                }
+            }
+        })
+        .await;
+        match response_builder {
+            Ok(response_builder) => Some(response_builder),
+            Err(error) if error.is_panic() => panic::resume_unwind(error.into_panic()),
+            Err(error) => Some(Err(FileRootError::Io(io::Error::other(error)))),
+        }
+    }
+
+    fn resolve(&self, relative_segments: &[String]) -> Result<Resolved, FileRootError> {
+        // A decoded slash within a segment is part of a name, which no file can have
+        if relative_segments
+            .iter()
+            .any(|segment| segment.contains('/'))
+        {
+            return Err(FileRootError::NotFound);
+        }
+        let relative_path = relative_segments.join("/");
+        let file_path = self.file_root.resolve(&relative_path)?;
+        if !file_path.is_dir() {
+            return Ok(Resolved::File(file_path));
+        }
+        if relative_segments
+            .last()
+            .map_or(true, |segment| !segment.is_empty())
+        {
+            return Ok(Resolved::DirectoryWithoutSlash);
+        }
+        let index_path = match relative_path.trim_end_matches('/') {
+            "" => INDEX_FILE_NAME.to_string(),
+            directory => format!("{directory}/{INDEX_FILE_NAME}"),
+        };
+        let index_path = self.file_root.resolve(&index_path)?;
+        match index_path.is_file() {
+            true => Ok(Resolved::File(index_path)),
+            false => Err(FileRootError::NotFound),
+        }
+    }
+
+    fn respond_with_file(
+        &self,
+        method: &Method,
+        file_path: &path::Path,
+        request_headers: &Headers,
+    ) -> io::Result<ResponseBuilder> {
+        let file = File::open(file_path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().ok();
+        let etag = modified.map(|modified| make_etag(modified, metadata.len()));
+
+        // Files are only ever read, so their preconditions are those of a GET
+        let response_builder =
+            match conditional::evaluate(&Method::Get, request_headers, etag.as_ref(), modified) {
+                Precondition::Passed => {
+                    let etag = etag.as_ref();
+                    self.read_file(method, file, file_path, &metadata, request_headers, etag)?
+                }
+                Precondition::NotModified => self.describe_file(file, file_path, &metadata)?,
+                Precondition::Failed => {
+                    return Ok(ResponseBuilder::default().set_status(Status::PreconditionFailed));
+                }
+            };
+
+        let response_builder = match etag {
+            Some(etag) => response_builder.set_etag(etag),
+            None => response_builder,
+        };
+        let response_builder = match modified {
+            Some(modified) => response_builder.set_last_modified(http_date::format(modified)),
+            None => response_builder,
+        };
+        Ok(response_builder)
+    }
+
+    // A 304 for the file, with the type and, unless it would have been streamed, length of the
+    // content it stands in for, so that it's only marked as compressed if that content would be
+    fn describe_file(
+        &self,
+        mut file: File,
+        file_path: &path::Path,
+        metadata: &Metadata,
+    ) -> io::Result<ResponseBuilder> {
+        let response_builder = ResponseBuilder::default()
+            .set_status(Status::NotModified)
+            .set_content_type(self.content_type(&mut file, file_path)?);
+        let response_builder = match metadata.len() {
+            length if length > MAX_BUFFERED_LENGTH => response_builder,
+            length => response_builder.set_content_length(length as usize),
+        };
+        Ok(response_builder)
+    }
+
+    fn content_type(&self, file: &mut File, file_path: &path::Path) -> io::Result<String> {
+        let media_type = match mime::from_path(file_path) {
+            Some(media_type) => media_type,
+            None if self.sniffs_content => {
+                let mut start = Vec::new();
+                file.take(SNIFF_LENGTH).read_to_end(&mut start)?;
+                file.rewind()?;
+                mime::sniff(&start).unwrap_or(mime::DEFAULT_MEDIA_TYPE)
+            }
+            None => mime::DEFAULT_MEDIA_TYPE,
+        };
+        Ok(mime::with_charset(media_type))
+    }
+
+    // Responds with the whole file or the requested ranges of it
+    fn read_file(
+        &self,
+        method: &Method,
+        mut file: File,
+        file_path: &path::Path,
+        metadata: &Metadata,
+        request_headers: &Headers,
+        etag: Option<&EntityTag>,
+    ) -> io::Result<ResponseBuilder> {
+        let length = metadata.len();
+        let content_type = self.content_type(&mut file, file_path)?;
+        // A response to HEAD has the headers of the one to GET, which depend on the content when
+        // it's compressed
+        let is_content_needed = *method != Method::Head
+            || compression::is_compressible(&HeaderValue::new(&content_type));
+
+        let range_specs = request_headers
+            .range()
+            .filter(|_| {
+                conditional::is_if_range_fresh(
+                    request_headers.if_range(),
+                    etag,
+                    metadata.modified().ok(),
+                )
+            })
+            .and_then(|range| range::parse(&range.to_string()));
+        let response_builder = match range_specs {
+            None if length > MAX_BUFFERED_LENGTH || !is_content_needed => {
+                let body_stream = BodyStream::from_reader(tokio::fs::File::from_std(file))
+                    .set_length(usize::try_from(length).unwrap_or(usize::MAX));
+                Response::ok()
+                    .set_body(body_stream)
+                    .set_content_type(content_type)
+            }
+            None => {
+                let mut content = Vec::new();
+                file.read_to_end(&mut content)?;
+                Response::ok()
+                    .set_body(content)
+                    .set_content_type(content_type)
+            }
+            Some(range_specs) => match range::resolve(&range_specs, length).as_slice() {
+                [] => ResponseBuilder::default()
+                    .set_status(Status::RangeNotSatisfiable)
+                    .set_content_range(range::unsatisfied_content_range(length)),
+                [range] => {
+                    file.seek(SeekFrom::Start(*range.start()))?;
+                    let range_length = range::length(range);
+                    let body_stream =
+                        BodyStream::from_reader(tokio::fs::File::from_std(file).take(range_length))
+                            .set_length(usize::try_from(range_length).unwrap_or(usize::MAX));
+                    ResponseBuilder::default()
+                        .set_status(Status::PartialContent)
+                        .set_body(body_stream)
+                        .set_content_type(content_type)
+                        .set_content_range(range::content_range(range, length))
+                }
+                ranges => {
+                    let boundary = format!("{:016x}", RandomState::new().build_hasher().finish());
+                    let mut parts = VecDeque::new();
+                    for range in ranges {
+                        let part_headers = format!(
+                            "--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+                            range::content_range(range, length)
+                        );
+                        parts.push_back(Part::Text(Bytes::from(part_headers)));
+                        parts.push_back(Part::Range {
+                            start: *range.start(),
+                            remaining: range::length(range),
+                            position: Position::Unsought,
+                        });
+                        parts.push_back(Part::Text(Bytes::from_static(b"\r\n")));
+                    }
+                    parts.push_back(Part::Text(Bytes::from(format!("--{boundary}--\r\n"))));
+                    let byte_ranges = ByteRanges {
+                        file: tokio::fs::File::from_std(file),
+                        parts,
+                    };
+                    let body_length = usize::try_from(byte_ranges.length()).unwrap_or(usize::MAX);
+                    ResponseBuilder::default()
+                        .set_status(Status::PartialContent)
+                        .set_body(BodyStream::from_reader(byte_ranges).set_length(body_length))
+                        .set_content_type(format!("multipart/byteranges; boundary={boundary}"))
+                }
+            },
+        };
+
+        Ok(response_builder.set_accept_ranges("bytes"))
+    }
+}
+
+// The body of a multipart/byteranges response, read part by part from a single handle on the file
+// so that no more than a chunk of it is held in memory
+// https://www.rfc-editor.org/rfc/rfc9110#section-14.6
+struct ByteRanges {
+    file: tokio::fs::File,
+    parts: VecDeque<Part>,
+}
+
+enum Part {
+    // Boundaries and part headers
+    Text(Bytes),
+    // The bytes of the file left to read, from `start` once the file has been seeked there
+    Range {
+        start: u64,
+        remaining: u64,
+        position: Position,
+    },
+}
+
+enum Position {
+    Unsought,
+    Seeking,
+    Sought,
+}
+
+impl ByteRanges {
+    fn length(&self) -> u64 {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.len() as u64,
+                Part::Range { remaining, .. } => *remaining,
+            })
+            .sum()
+    }
+}
+
+impl AsyncRead for ByteRanges {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        while let Some(part) = this.parts.front_mut() {
+            match part {
+                Part::Text(text) if !text.is_empty() => {
+                    let length = text.len().min(buf.remaining());
+                    buf.put_slice(&text[..length]);
+                    text.advance(length);
+                    return Poll::Ready(Ok(()));
+                }
+                Part::Range {
+                    start,
+                    remaining,
+                    position,
+                } if *remaining > 0 => {
+                    let mut file = Pin::new(&mut this.file);
+                    if let Position::Unsought = position {
+                        file.as_mut().start_seek(SeekFrom::Start(*start))?;
+                        *position = Position::Seeking;
+                    }
+                    if let Position::Seeking = position {
+                        ready!(file.as_mut().poll_complete(cx))?;
+                        *position = Position::Sought;
+                    }
+                    let length = usize::try_from(*remaining)
+                        .unwrap_or(usize::MAX)
+                        .min(buf.remaining());
+                    let mut chunk = vec![0; length];
+                    let mut chunk_buf = ReadBuf::new(&mut chunk);
+                    ready!(file.poll_read(cx, &mut chunk_buf))?;
+                    let read = chunk_buf.filled();
+                    if read.is_empty() {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "The file is shorter than the ranges being read from it",
+                        )));
+                    }
+                    *remaining -= read.len() as u64;
+                    buf.put_slice(read);
+                    return Poll::Ready(Ok(()));
+                }
+                _ => {}
+            }
+            this.parts.pop_front();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+enum Resolved {
+    File(PathBuf),
+    // Relative links in the index of a directory requested without a trailing slash would
+    // resolve against its parent, so the client is sent to the path with the slash instead
+    DirectoryWithoutSlash,
+}
+
+// Like most servers, tags files by their modification time and length rather than hashing their
+// content, which would mean reading the whole file to answer a conditional request
+fn make_etag(modified: SystemTime, length: u64) -> EntityTag {
+    let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    EntityTag::strong(format!(
+        "{:x}.{:x}-{length:x}",
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::{
+        body::Body,
+        headers::{HeaderName, HeaderValue},
+    };
+
+    use super::*;
+
+    // Creates a directory for each test, containing a small static site
+    fn make_static_files(name: &str) -> StaticFiles {
+        let directory = env::temp_dir().join(format!("static-files-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("docs")).unwrap();
+        fs::create_dir_all(directory.join("empty")).unwrap();
+        fs::write(directory.join("index.html"), "<!doctype html>").unwrap();
+        fs::write(directory.join("docs/index.html"), "<!doctype html>").unwrap();
+        fs::write(directory.join("site.css"), "body {}").unwrap();
+        fs::write(directory.join("image"), b"\x89PNG\r\n\x1a\n").unwrap();
+        fs::write(directory.join("digits.txt"), "0123456789").unwrap();
+        StaticFiles::new(FileRoot::new(directory).unwrap()).set_mount_point("static")
+    }
+
+    fn content_type(response_builder: ResponseBuilder) -> Option<HeaderValue> {
+        response_builder.build().headers().content_type().cloned()
+    }
+
+    #[tokio::test]
+    async fn content_type_from_extension() {
+        let static_files = make_static_files("extension");
+
+        let response_builder = static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/static/site.css"),
+                &Headers::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            content_type(response_builder),
+            Some(HeaderValue::new("text/css; charset=utf-8"))
+        );
+    }
+
+    #[tokio::test]
+    async fn content_type_is_only_sniffed_when_enabled() {
+        let static_files = make_static_files("sniff");
+
+        let response_builder = static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/static/image"),
+                &Headers::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            content_type(response_builder),
+            Some(HeaderValue::new("application/octet-stream"))
+        );
+
+        let static_files = static_files.set_sniffs_content(true);
+
+        let response_builder = static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/static/image"),
+                &Headers::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            content_type(response_builder),
+            Some(HeaderValue::new("image/png"))
+        );
+    }
+
+    #[tokio::test]
+    async fn directories_serve_their_index() {
+        let static_files = make_static_files("index");
+
+        for path in ["/static/", "/static/docs/"] {
+            let response = static_files
+                .serve(&Method::Get, &Path::new(path), &Headers::default())
+                .await
+                .unwrap()
+                .unwrap()
+                .build();
+
+            assert_eq!(
+                response.body().map(|body| body.as_bytes()),
+                Some(&b"<!doctype html>"[..]),
+                "Serving {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn file_names_are_decoded() {
+        let static_files = make_static_files("decoded");
+        fs::write(static_files.file_root().root().join("100% a.txt"), "a").unwrap();
+
+        let response = static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/static/100%25%20a.txt"),
+                &Headers::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap()
+            .build();
+
+        assert_eq!(response.body(), Some(&Body::from(b"a".to_vec())));
+    }
+
+    #[tokio::test]
+    async fn directories_without_a_trailing_slash_are_redirected() {
+        let static_files = make_static_files("redirect");
+
+        for (path, expected_location) in [
+            ("/static/docs", "/static/docs/"),
+            ("/static/./docs?lang=en", "/static/./docs/?lang=en"),
+            ("/static", "/static/"),
+        ] {
+            let response = static_files
+                .serve(&Method::Get, &Path::new(path), &Headers::default())
+                .await
+                .unwrap()
+                .unwrap()
+                .build();
+
+            assert!(
+                response
+                    .to_string()
+                    .starts_with("HTTP/1.1 301 Moved Permanently"),
+                "Serving {path}"
+            );
+            assert_eq!(
+                response.headers().get(&HeaderName::Location),
+                Some(&HeaderValue::new(expected_location)),
+                "Serving {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_files_and_indexes_are_not_found() {
+        let static_files = make_static_files("missing");
+
+        for path in [
+            "/static/missing.css",
+            "/static/empty/",
+            "/static/docs%2Findex.html",
+            "/static/a%2F..%2F..%2Fsite.css",
+        ] {
+            let served = static_files
+                .serve(&Method::Get, &Path::new(path), &Headers::default())
+                .await
+                .unwrap();
+
+            assert!(
+                matches!(served, Err(FileRootError::NotFound)),
+                "Serving {path}"
+            );
+        }
+        assert!(static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/other/site.css"),
+                &Headers::default()
+            )
+            .await
+            .is_none());
+    }
+
+    async fn serve_range(
+        static_files: &StaticFiles,
+        range: &str,
+        if_range: Option<&str>,
+    ) -> Response {
+        let mut headers = Headers::default();
+        headers.append(HeaderName::Range, HeaderValue::new(range));
+        if let Some(if_range) = if_range {
+            headers.append(HeaderName::IfRange, HeaderValue::new(if_range));
+        }
+        static_files
+            .serve(&Method::Get, &Path::new("/static/digits.txt"), &headers)
+            .await
+            .unwrap()
+            .unwrap()
+            .build()
+    }
+
+    // Reads the whole of a streamed body
+    async fn read_stream(response: &Response) -> Vec<u8> {
+        let Some(Body::Stream(body_stream)) = response.body() else {
+            panic!("Response body is not streamed");
+        };
+        let mut source = body_stream.take_source().unwrap();
+        let mut content = Vec::new();
+        while let Some(chunk) = source.next_chunk().await.unwrap() {
+            content.extend(chunk);
+        }
+        assert_eq!(body_stream.length(), Some(content.len()));
+        content
+    }
+
+    #[tokio::test]
+    async fn single_range_is_partial_content() {
+        let static_files = make_static_files("single-range");
+
+        for (range, expected_content_range, expected_body) in [
+            ("bytes=2-4", "bytes 2-4/10", "234"),
+            ("bytes=7-", "bytes 7-9/10", "789"),
+            ("bytes=-2", "bytes 8-9/10", "89"),
+            ("bytes=8-100", "bytes 8-9/10", "89"),
+        ] {
+            let response = serve_range(&static_files, range, None).await;
+
+            assert!(response
+                .to_string()
+                .starts_with("HTTP/1.1 206 Partial Content"));
+            assert_eq!(
+                response.headers().get(&HeaderName::ContentRange),
+                Some(&HeaderValue::new(expected_content_range)),
+                "Serving {range}"
+            );
+            assert_eq!(
+                read_stream(&response).await,
+                expected_body.as_bytes(),
+                "Serving {range}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn multiple_ranges_are_multipart() {
+        let static_files = make_static_files("multiple-ranges");
+
+        let response = serve_range(&static_files, "bytes=0-1, -1", None).await;
+
+        let content_type = response.headers().content_type().unwrap().to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        let expected_body = format!(
+            "--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 9-9/10\r\n\r\n9\r\n\
+             --{boundary}--\r\n"
+        );
+        assert_eq!(read_stream(&response).await, expected_body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn overlapping_ranges_are_coalesced() {
+        let static_files = make_static_files("overlapping-ranges");
+
+        let response = serve_range(&static_files, "bytes=4-6, 0-1, 2-3, 5-", None).await;
+
+        assert_eq!(
+            response.headers().get(&HeaderName::ContentRange),
+            Some(&HeaderValue::new("bytes 0-9/10"))
+        );
+        assert_eq!(read_stream(&response).await, b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn unsatisfiable_range() {
+        let static_files = make_static_files("unsatisfiable-range");
+
+        let response = serve_range(&static_files, "bytes=10-, -0", None).await;
+
+        assert!(response
+            .to_string()
+            .starts_with("HTTP/1.1 416 Range Not Satisfiable"));
+        assert_eq!(
+            response.headers().get(&HeaderName::ContentRange),
+            Some(&HeaderValue::new("bytes */10"))
+        );
+    }
+
+    #[tokio::test]
+    async fn range_is_ignored_unless_if_range_matches() {
+        let static_files = make_static_files("if-range");
+        let response = serve_range(&static_files, "bytes=0-0", None).await;
+        let etag = response.headers().etag().unwrap().to_string();
+        let last_modified = response.headers().last_modified().unwrap().to_string();
+
+        for (if_range, is_partial) in [
+            (etag.as_str(), true),
+            (&format!("W/{etag}"), false),
+            (last_modified.as_str(), true),
+            ("Thu, 01 Jan 1970 00:00:00 GMT", false),
+            ("\"some-etag\"", false),
+        ] {
+            let response = serve_range(&static_files, "bytes=0-0", Some(if_range)).await;
+
+            assert_eq!(
+                response.to_string().starts_with("HTTP/1.1 206"),
+                is_partial,
+                "If-Range: {if_range}"
+            );
+        }
+    }
+
+    async fn serve_conditional(
+        static_files: &StaticFiles,
+        header_name: HeaderName,
+        value: &str,
+    ) -> Response {
+        let mut headers = Headers::default();
+        headers.append(header_name, HeaderValue::new(value));
+        static_files
+            .serve(&Method::Get, &Path::new("/static/digits.txt"), &headers)
+            .await
+            .unwrap()
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn large_files_are_streamed() {
+        let static_files = make_static_files("large");
+        let length = MAX_BUFFERED_LENGTH as usize + 1;
+        let file_path = static_files.file_root().root().join("large.bin");
+        fs::write(file_path, vec![0; length]).unwrap();
+
+        let response = static_files
+            .serve(
+                &Method::Get,
+                &Path::new("/static/large.bin"),
+                &Headers::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap()
+            .build();
+
+        assert!(matches!(response.body(), Some(Body::Stream(_))));
+        assert_eq!(
+            response.headers().get(&HeaderName::ContentLength),
+            Some(&HeaderValue::new(length.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn head_requests_have_the_headers_of_get_requests() {
+        let static_files = make_static_files("head");
+        let text = "Hello, World! ".repeat(200);
+        fs::write(static_files.file_root().root().join("hello.txt"), &text).unwrap();
+        fs::write(
+            static_files.file_root().root().join("logo.png"),
+            b"\x89PNG\r\n\x1a\n",
+        )
+        .unwrap();
+        let mut request_headers = Headers::default();
+        request_headers.append(HeaderName::AcceptEncoding, HeaderValue::new("gzip"));
+        let serve = |method, path| {
+            let static_files = static_files.clone();
+            let request_headers = request_headers.clone();
+            async move {
+                static_files
+                    .serve(&method, &Path::new(path), &request_headers)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .compress(request_headers.accept_encoding())
+                    .build()
+            }
+        };
+
+        for path in [
+            "/static/hello.txt",
+            "/static/digits.txt",
+            "/static/logo.png",
+        ] {
+            let get_response = serve(Method::Get, path).await;
+            let head_response = serve(Method::Head, path).await;
+
+            assert_eq!(head_response.headers(), get_response.headers(), "{path}");
+        }
+        let head_response = serve(Method::Head, "/static/hello.txt").await;
+        assert_eq!(
+            head_response.headers().content_encoding(),
+            Some(&HeaderValue::new("gzip"))
+        );
+
+        // Files that wouldn't be compressed aren't read, but only streamed if the body is sent
+        let head_response = serve(Method::Head, "/static/logo.png").await;
+        assert!(matches!(head_response.body(), Some(Body::Stream(_))));
+        assert_eq!(read_stream(&head_response).await, b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[tokio::test]
+    async fn files_have_validators() {
+        let static_files = make_static_files("validators");
+
+        let response = serve_range(&static_files, "bytes=0-0", None).await;
+
+        let etag = response.headers().etag().unwrap().to_string();
+        assert!(EntityTag::parse(&etag).is_some_and(|etag| !etag.is_weak()));
+        assert!(response
+            .headers()
+            .last_modified()
+            .is_some_and(|last_modified| http_date::parse(&last_modified.to_string()).is_some()));
+    }
+
+    #[tokio::test]
+    async fn cached_copies_are_not_modified() {
+        let static_files = make_static_files("not-modified");
+        let response = serve_range(&static_files, "bytes=0-0", None).await;
+        let etag = response.headers().etag().unwrap().to_string();
+        let last_modified = response.headers().last_modified().unwrap().to_string();
+
+        for (header_name, value) in [
+            (HeaderName::IfNoneMatch, etag.as_str()),
+            (HeaderName::IfModifiedSince, last_modified.as_str()),
+        ] {
+            let response = serve_conditional(&static_files, header_name, value).await;
+
+            assert!(response
+                .to_string()
+                .starts_with("HTTP/1.1 304 Not Modified"));
+            assert_eq!(response.body(), None);
+            assert_eq!(response.headers().etag(), Some(&HeaderValue::new(&etag)));
+            assert_eq!(
+                response.headers().get(&HeaderName::ContentLength),
+                Some(&HeaderValue::new("10"))
+            );
+        }
+
+        let response = serve_conditional(&static_files, HeaderName::IfNoneMatch, "\"stale\"").await;
+
+        assert!(response.to_string().starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn failed_preconditions() {
+        let static_files = make_static_files("precondition-failed");
+
+        for (header_name, value) in [
+            (HeaderName::IfMatch, "\"stale\""),
+            (
+                HeaderName::IfUnmodifiedSince,
+                "Thu, 01 Jan 1970 00:00:00 GMT",
+            ),
+        ] {
+            let response = serve_conditional(&static_files, header_name, value).await;
+
+            assert!(response
+                .to_string()
+                .starts_with("HTTP/1.1 412 Precondition Failed"));
+        }
+    }
+}
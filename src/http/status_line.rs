@@ -1,8 +1,12 @@
-use std::fmt;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 use crate::version::Version;
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 pub struct StatusLine {
     http_version: Version,
     status: Status,
@@ -43,6 +47,10 @@ impl StatusLine {
             status,
         }
     }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
 }
 
 impl fmt::Display for StatusLine {
@@ -51,30 +59,330 @@ impl fmt::Display for StatusLine {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+// The status codes in the IANA registry, plus any other three digit code with its own reason.
+// Statuses are compared by code alone, so a custom reason doesn't make a 404 any less a 404.
+// https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml
+#[derive(Clone, Debug, Default)]
 pub enum Status {
+    Continue,
+    SwitchingProtocols,
+    Processing,
+    EarlyHints,
+
     #[default]
     Ok,
-    NotFound,
-    InternalServerError,
     Created,
+    Accepted,
+    NonAuthoritativeInformation,
+    NoContent,
+    ResetContent,
+    PartialContent,
+    MultiStatus,
+    AlreadyReported,
+    ImUsed,
+
+    MultipleChoices,
+    MovedPermanently,
+    Found,
+    SeeOther,
+    NotModified,
+    UseProxy,
+    TemporaryRedirect,
+    PermanentRedirect,
+
     BadRequest,
+    Unauthorized,
+    PaymentRequired,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    ProxyAuthenticationRequired,
+    RequestTimeout,
+    Conflict,
+    Gone,
+    LengthRequired,
+    PreconditionFailed,
+    ContentTooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    ExpectationFailed,
+    MisdirectedRequest,
+    UnprocessableContent,
+    Locked,
+    FailedDependency,
+    TooEarly,
+    UpgradeRequired,
+    PreconditionRequired,
+    TooManyRequests,
+    RequestHeaderFieldsTooLarge,
+    UnavailableForLegalReasons,
+
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+    HttpVersionNotSupported,
+    VariantAlsoNegotiates,
+    InsufficientStorage,
+    LoopDetected,
+    NotExtended,
+    NetworkAuthenticationRequired,
+    // A code outside the registry, or a registered one with a different reason. Only made by
+    // `from_code` and `custom`, so that the reason is always safe to write.
+    Custom(CustomStatus),
 }
 
-impl fmt::Display for Status {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct CustomStatus {
+    code: u16,
+    reason: String,
+}
+
+impl Status {
+    const KNOWN: &'static [Self] = &[
+        Self::Continue,
+        Self::SwitchingProtocols,
+        Self::Processing,
+        Self::EarlyHints,
+        Self::Ok,
+        Self::Created,
+        Self::Accepted,
+        Self::NonAuthoritativeInformation,
+        Self::NoContent,
+        Self::ResetContent,
+        Self::PartialContent,
+        Self::MultiStatus,
+        Self::AlreadyReported,
+        Self::ImUsed,
+        Self::MultipleChoices,
+        Self::MovedPermanently,
+        Self::Found,
+        Self::SeeOther,
+        Self::NotModified,
+        Self::UseProxy,
+        Self::TemporaryRedirect,
+        Self::PermanentRedirect,
+        Self::BadRequest,
+        Self::Unauthorized,
+        Self::PaymentRequired,
+        Self::Forbidden,
+        Self::NotFound,
+        Self::MethodNotAllowed,
+        Self::NotAcceptable,
+        Self::ProxyAuthenticationRequired,
+        Self::RequestTimeout,
+        Self::Conflict,
+        Self::Gone,
+        Self::LengthRequired,
+        Self::PreconditionFailed,
+        Self::ContentTooLarge,
+        Self::UriTooLong,
+        Self::UnsupportedMediaType,
+        Self::RangeNotSatisfiable,
+        Self::ExpectationFailed,
+        Self::MisdirectedRequest,
+        Self::UnprocessableContent,
+        Self::Locked,
+        Self::FailedDependency,
+        Self::TooEarly,
+        Self::UpgradeRequired,
+        Self::PreconditionRequired,
+        Self::TooManyRequests,
+        Self::RequestHeaderFieldsTooLarge,
+        Self::UnavailableForLegalReasons,
+        Self::InternalServerError,
+        Self::NotImplemented,
+        Self::BadGateway,
+        Self::ServiceUnavailable,
+        Self::GatewayTimeout,
+        Self::HttpVersionNotSupported,
+        Self::VariantAlsoNegotiates,
+        Self::InsufficientStorage,
+        Self::LoopDetected,
+        Self::NotExtended,
+        Self::NetworkAuthenticationRequired,
+    ];
+
+    // Returns the registered status for `code`, or a custom one with an empty reason for other
+    // three digit codes
+    pub fn from_code(code: u16) -> Option<Self> {
+        if !(100..=999).contains(&code) {
+            return None;
+        }
+        let status = Self::KNOWN
+            .iter()
+            .find(|known| known.code() == code)
+            .cloned()
+            .unwrap_or(Self::Custom(CustomStatus {
+                code,
+                reason: String::new(),
+            }));
+        Some(status)
+    }
+
+    // A status with its own reason, which can't contain control characters since it's written
+    // as-is into the status line
+    // https://www.rfc-editor.org/rfc/rfc9112#section-4
+    pub fn custom(code: u16, reason: impl Into<String>) -> Option<Self> {
+        let reason = reason.into();
+        let is_valid = (100..=999).contains(&code)
+            && !reason
+                .chars()
+                .any(|character| character.is_control() && character != '\t');
+        is_valid.then_some(Self::Custom(CustomStatus { code, reason }))
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Custom(custom_status) => custom_status.code,
+            known => known.registered().0,
+        }
+    }
+
+    pub fn reason(&self) -> &str {
+        match self {
+            Self::Custom(custom_status) => &custom_status.reason,
+            known => known.registered().1,
+        }
+    }
+
+    // 1xx
+    pub fn is_informational(&self) -> bool {
+        self.code() / 100 == 1
+    }
+
+    // 2xx
+    pub fn is_success(&self) -> bool {
+        self.code() / 100 == 2
+    }
+
+    // 3xx
+    pub fn is_redirection(&self) -> bool {
+        self.code() / 100 == 3
+    }
+
+    // 4xx
+    pub fn is_client_error(&self) -> bool {
+        self.code() / 100 == 4
+    }
+
+    // 5xx
+    pub fn is_server_error(&self) -> bool {
+        self.code() / 100 == 5
+    }
+
+    // 4xx or 5xx
+    pub fn is_error(&self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
+
+    fn registered(&self) -> (u16, &'static str) {
         match self {
-            Self::Ok => write!(f, "200 OK"),
-            Self::NotFound => write!(f, "404 Not Found"),
-            Self::InternalServerError => write!(f, "500 Internal Server Error"),
-            Self::Created => write!(f, "201 Created"),
-            Self::BadRequest => write!(f, "400 Bad Request"),
+            Self::Continue => (100, "Continue"),
+            Self::SwitchingProtocols => (101, "Switching Protocols"),
+            Self::Processing => (102, "Processing"),
+            Self::EarlyHints => (103, "Early Hints"),
+            Self::Ok => (200, "OK"),
+            Self::Created => (201, "Created"),
+            Self::Accepted => (202, "Accepted"),
+            Self::NonAuthoritativeInformation => (203, "Non-Authoritative Information"),
+            Self::NoContent => (204, "No Content"),
+            Self::ResetContent => (205, "Reset Content"),
+            Self::PartialContent => (206, "Partial Content"),
+            Self::MultiStatus => (207, "Multi-Status"),
+            Self::AlreadyReported => (208, "Already Reported"),
+            Self::ImUsed => (226, "IM Used"),
+            Self::MultipleChoices => (300, "Multiple Choices"),
+            Self::MovedPermanently => (301, "Moved Permanently"),
+            Self::Found => (302, "Found"),
+            Self::SeeOther => (303, "See Other"),
+            Self::NotModified => (304, "Not Modified"),
+            Self::UseProxy => (305, "Use Proxy"),
+            Self::TemporaryRedirect => (307, "Temporary Redirect"),
+            Self::PermanentRedirect => (308, "Permanent Redirect"),
+            Self::BadRequest => (400, "Bad Request"),
+            Self::Unauthorized => (401, "Unauthorized"),
+            Self::PaymentRequired => (402, "Payment Required"),
+            Self::Forbidden => (403, "Forbidden"),
+            Self::NotFound => (404, "Not Found"),
+            Self::MethodNotAllowed => (405, "Method Not Allowed"),
+            Self::NotAcceptable => (406, "Not Acceptable"),
+            Self::ProxyAuthenticationRequired => (407, "Proxy Authentication Required"),
+            Self::RequestTimeout => (408, "Request Timeout"),
+            Self::Conflict => (409, "Conflict"),
+            Self::Gone => (410, "Gone"),
+            Self::LengthRequired => (411, "Length Required"),
+            Self::PreconditionFailed => (412, "Precondition Failed"),
+            Self::ContentTooLarge => (413, "Content Too Large"),
+            Self::UriTooLong => (414, "URI Too Long"),
+            Self::UnsupportedMediaType => (415, "Unsupported Media Type"),
+            Self::RangeNotSatisfiable => (416, "Range Not Satisfiable"),
+            Self::ExpectationFailed => (417, "Expectation Failed"),
+            Self::MisdirectedRequest => (421, "Misdirected Request"),
+            Self::UnprocessableContent => (422, "Unprocessable Content"),
+            Self::Locked => (423, "Locked"),
+            Self::FailedDependency => (424, "Failed Dependency"),
+            Self::TooEarly => (425, "Too Early"),
+            Self::UpgradeRequired => (426, "Upgrade Required"),
+            Self::PreconditionRequired => (428, "Precondition Required"),
+            Self::TooManyRequests => (429, "Too Many Requests"),
+            Self::RequestHeaderFieldsTooLarge => (431, "Request Header Fields Too Large"),
+            Self::UnavailableForLegalReasons => (451, "Unavailable For Legal Reasons"),
+            Self::InternalServerError => (500, "Internal Server Error"),
+            Self::NotImplemented => (501, "Not Implemented"),
+            Self::BadGateway => (502, "Bad Gateway"),
+            Self::ServiceUnavailable => (503, "Service Unavailable"),
+            Self::GatewayTimeout => (504, "Gateway Timeout"),
+            Self::HttpVersionNotSupported => (505, "HTTP Version Not Supported"),
+            Self::VariantAlsoNegotiates => (506, "Variant Also Negotiates"),
+            Self::InsufficientStorage => (507, "Insufficient Storage"),
+            Self::LoopDetected => (508, "Loop Detected"),
+            Self::NotExtended => (510, "Not Extended"),
+            Self::NetworkAuthenticationRequired => (511, "Network Authentication Required"),
+            Self::Custom(_) => unreachable!("Custom statuses aren't registered"),
         }
     }
 }
 
+impl PartialEq for Status {
+    fn eq(&self, other: &Self) -> bool {
+        self.code() == other.code()
+    }
+}
+
+impl Eq for Status {}
+
+impl Ord for Status {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.code().cmp(&other.code())
+    }
+}
+
+impl PartialOrd for Status {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Status {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.code().hash(state);
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::hash::DefaultHasher;
+
     use super::*;
 
     #[test]
@@ -85,4 +393,69 @@ mod tests {
             String::from("HTTP/1.1 200 OK\r\n")
         );
     }
+
+    #[test]
+    fn registered_statuses_round_trip_through_their_codes() {
+        for status in Status::KNOWN {
+            assert_eq!(Status::from_code(status.code()).as_ref(), Some(status));
+            assert_eq!(
+                status.to_string(),
+                format!("{} {}", status.code(), status.reason())
+            );
+        }
+        assert_eq!(Status::NotFound.to_string(), "404 Not Found");
+        assert_eq!(Status::ImUsed.reason(), "IM Used");
+    }
+
+    #[test]
+    fn unregistered_codes_are_custom() {
+        let status = Status::from_code(299).unwrap();
+
+        assert_eq!(status.code(), 299);
+        assert_eq!(status.reason(), "");
+        assert_eq!(
+            StatusLine::make_http_1_1_status_line(status).to_string(),
+            "HTTP/1.1 299 \r\n"
+        );
+        assert_eq!(Status::from_code(99), None);
+        assert_eq!(Status::from_code(1000), None);
+    }
+
+    #[test]
+    fn custom_reasons() {
+        let status = Status::custom(200, "All Good").unwrap();
+
+        assert_eq!(status.to_string(), "200 All Good");
+        assert!(status.is_success());
+        assert_eq!(Status::custom(200, "Bad\r\nX-Injected: true"), None);
+        assert_eq!(Status::custom(42, "Answer"), None);
+    }
+
+    #[test]
+    fn statuses_are_compared_by_code() {
+        let custom_not_found = Status::custom(404, "Nothing Here").unwrap();
+        let hash = |status: &Status| {
+            let mut hasher = DefaultHasher::new();
+            status.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(custom_not_found, Status::NotFound);
+        assert_eq!(hash(&custom_not_found), hash(&Status::NotFound));
+        assert_eq!(Status::from_code(404), Some(custom_not_found));
+        assert_ne!(Status::from_code(499), Status::from_code(498));
+        assert!(Status::Ok < Status::custom(201, "Made").unwrap());
+    }
+
+    #[test]
+    fn classify_statuses() {
+        assert!(Status::Continue.is_informational());
+        assert!(Status::NoContent.is_success());
+        assert!(Status::PermanentRedirect.is_redirection());
+        assert!(Status::TooManyRequests.is_client_error());
+        assert!(Status::ServiceUnavailable.is_server_error());
+        assert!(Status::Conflict.is_error());
+        assert!(!Status::Ok.is_error());
+        assert!(!Status::from_code(999).unwrap().is_error());
+    }
 }
@@ -34,15 +34,15 @@ impl fmt::Display for Version {
 }
 
 fn http(bytes: &[u8]) -> IResult<&[u8], &[u8]> {
-    bytes::complete::tag(b"HTTP/")(bytes)
+    bytes::streaming::tag(b"HTTP/")(bytes)
 }
 
 fn sem_ver(bytes: &[u8]) -> IResult<&[u8], Version> {
     branch::alt((
-        combinator::map(bytes::complete::tag(b"0.9"), |_| Version::ZeroPointNine),
-        combinator::map(bytes::complete::tag(b"1.0"), |_| Version::OnePointZero),
-        combinator::map(bytes::complete::tag(b"1.1"), |_| Version::OnePointOne),
-        combinator::map(bytes::complete::tag(b"2"), |_| Version::Two),
-        combinator::map(bytes::complete::tag(b"3"), |_| Version::Three),
+        combinator::map(bytes::streaming::tag(b"0.9"), |_| Version::ZeroPointNine),
+        combinator::map(bytes::streaming::tag(b"1.0"), |_| Version::OnePointZero),
+        combinator::map(bytes::streaming::tag(b"1.1"), |_| Version::OnePointOne),
+        combinator::map(bytes::streaming::tag(b"2"), |_| Version::Two),
+        combinator::map(bytes::streaming::tag(b"3"), |_| Version::Three),
     ))(bytes)
 }